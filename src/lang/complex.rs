@@ -0,0 +1,129 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0., im: 1. };
+
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.re == 0. && self.im == 0.
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.re.is_finite() && self.im.is_finite()
+    }
+
+    /// Principal square root.
+    pub fn sqrt(self) -> Self {
+        let abs = self.abs();
+        let re = ((abs + self.re) / 2.).sqrt();
+        let im = ((abs - self.re) / 2.).sqrt();
+        Self::new(re, if self.im < 0. { -im } else { im })
+    }
+
+    pub fn exp(self) -> Self {
+        let abs = self.re.exp();
+        Self::new(abs * self.im.cos(), abs * self.im.sin())
+    }
+
+    /// Principal natural logarithm.
+    pub fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let norm = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / norm,
+            (self.im * rhs.re - self.re * rhs.im) / norm,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl Complex {
+    /// Writes the number with both parts written by `number`.
+    pub fn format_with(&self, number: impl Fn(f64) -> String) -> String {
+        let im = match self.im.abs() {
+            1. => String::new(),
+            im => number(im),
+        };
+        match (self.re, self.im < 0.) {
+            (0., false) => format!("{im}i"),
+            (0., true) => format!("-{im}i"),
+            (re, false) => format!("{}+{im}i", number(re)),
+            (re, true) => format!("{}-{im}i", number(re)),
+        }
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_with(|part| part.to_string()))
+    }
+}
@@ -0,0 +1,216 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+use std::str::FromStr;
+
+/// Largest number of fractional digits a decimal may carry.
+pub const MAX_SCALE: u32 = 28;
+
+/// How digits beyond the scale are discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Rounding {
+    /// Ties go to the even neighbour (banker's rounding).
+    #[default]
+    HalfEven,
+    /// Ties go away from zero.
+    HalfUp,
+    /// Digits are dropped, rounding towards zero.
+    Truncate,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-even" => Ok(Rounding::HalfEven),
+            "half-up" => Ok(Rounding::HalfUp),
+            "truncate" => Ok(Rounding::Truncate),
+            other => Err(format!(
+                "Unknown rounding mode '{other}', expected 'half-even', 'half-up' or 'truncate'"
+            )),
+        }
+    }
+}
+
+/// Settings of the decimal fixed-point mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecimalMode {
+    /// Number of fractional digits results are rounded to.
+    pub scale: u32,
+    pub rounding: Rounding,
+}
+
+impl Default for DecimalMode {
+    fn default() -> Self {
+        Self {
+            scale: 2,
+            rounding: Rounding::HalfEven,
+        }
+    }
+}
+
+/// Base-10 number `units / 10^scale`.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    pub units: i128,
+    pub scale: u32,
+}
+
+impl Decimal {
+    pub fn new(units: i128, scale: u32) -> Self {
+        Self { units, scale }
+    }
+
+    /// The decimal with the shortest representation that converts back to `value`.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        let text = value.to_string();
+        let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+        let scale = u32::try_from(frac.len())
+            .ok()
+            .filter(|&scale| scale <= MAX_SCALE)?;
+        let units = format!("{int}{frac}").parse().ok()?;
+        Some(Self::new(units, scale))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        // Parsing the text is correctly rounded, unlike dividing by a power of ten
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.units == 0
+    }
+
+    /// Rounds (or extends) to exactly `scale` fractional digits.
+    pub fn round(self, scale: u32, rounding: Rounding) -> Option<Self> {
+        if scale >= self.scale {
+            let factor = 10i128.checked_pow(scale - self.scale)?;
+            return Some(Self::new(self.units.checked_mul(factor)?, scale));
+        }
+        let divisor = 10i128.checked_pow(self.scale - scale)?;
+        Some(Self::new(divide(self.units, divisor, rounding), scale))
+    }
+
+    /// Brings both operands to the same scale.
+    fn align(self, rhs: Self) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(rhs.scale);
+        let lhs = self.round(scale, Rounding::Truncate)?;
+        let rhs = rhs.round(scale, Rounding::Truncate)?;
+        Some((lhs.units, rhs.units, scale))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.align(rhs)?;
+        Some(Self::new(lhs.checked_add(rhs)?, scale))
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.align(rhs)?;
+        Some(Self::new(lhs.checked_sub(rhs)?, scale))
+    }
+
+    /// Multiplies, rounding the product by `rounding` when its scale exceeds
+    /// [`MAX_SCALE`]. `None` on overflow.
+    pub fn checked_mul(self, rhs: Self, rounding: Rounding) -> Option<Self> {
+        let scale = self.scale + rhs.scale;
+        if scale > MAX_SCALE {
+            return Self::new(self.units.checked_mul(rhs.units)?, scale).round(MAX_SCALE, rounding);
+        }
+        Some(Self::new(self.units.checked_mul(rhs.units)?, scale))
+    }
+
+    /// Divides, rounding the quotient to `scale` fractional digits.
+    /// `None` on division by zero or overflow.
+    pub fn checked_div(self, rhs: Self, scale: u32, rounding: Rounding) -> Option<Self> {
+        if rhs.is_zero() {
+            return None;
+        }
+        // units = self.units * 10^(scale + rhs.scale - self.scale) / rhs.units
+        let shift = scale as i64 + rhs.scale as i64 - self.scale as i64;
+        let (num, den) = if shift >= 0 {
+            (
+                self.units.checked_mul(10i128.checked_pow(shift as u32)?)?,
+                rhs.units,
+            )
+        } else {
+            (
+                self.units,
+                rhs.units
+                    .checked_mul(10i128.checked_pow((-shift) as u32)?)?,
+            )
+        };
+        Some(Self::new(divide(num, den, rounding), scale))
+    }
+}
+
+/// Integer division `num / den` rounded according to `rounding`.
+fn divide(num: i128, den: i128, rounding: Rounding) -> i128 {
+    let (quotient, remainder) = (num / den, num % den);
+    if remainder == 0 {
+        return quotient;
+    }
+    let sign = if (num < 0) == (den < 0) { 1 } else { -1 };
+    let twice = remainder.unsigned_abs() * 2;
+    let round_away = match rounding {
+        Rounding::Truncate => false,
+        Rounding::HalfUp => twice >= den.unsigned_abs(),
+        Rounding::HalfEven => {
+            twice > den.unsigned_abs() || (twice == den.unsigned_abs() && quotient % 2 != 0)
+        }
+    };
+    if round_away {
+        quotient + sign
+    } else {
+        quotient
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.align(*other) {
+            Some((lhs, rhs, _)) => lhs.cmp(&rhs),
+            // Aligning overflows only if the magnitudes differ widely
+            None => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.units, self.scale)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let digits = self.units.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{int}.{frac}")
+    }
+}
@@ -0,0 +1,194 @@
+use std::str::FromStr;
+
+use super::{Decimal, Value};
+
+/// How numbers are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// Shortest representation that reads back to the same number.
+    #[default]
+    Auto,
+    /// Fixed number of decimal places.
+    Fixed(usize),
+    /// Rounded to a number of significant digits.
+    Significant(usize),
+    /// Mantissa between 1 and 10 with an exponent, e.g. `1.5e3`, optionally
+    /// with a fixed number of mantissa decimals.
+    Scientific(Option<usize>),
+    /// Like scientific, with the exponent a multiple of three, e.g. `15e3`.
+    Engineering(Option<usize>),
+    /// Integers in hexadecimal, e.g. `0xff`.
+    Hex,
+    /// Integers in binary, e.g. `0b101`.
+    Binary,
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    /// Parses `auto`, `fixed N`, `sig N`, `sci [N]`, `eng [N]`, `hex` or `bin`;
+    /// the digits may also follow a colon, as in `fixed:2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split([' ', ':']).filter(|part| !part.is_empty());
+        let name = parts.next().unwrap_or_default();
+        let digits = match parts.next().map(str::parse::<usize>) {
+            Some(Ok(digits)) if digits <= 17 => Some(digits),
+            Some(_) => return Err("Expected a number of digits between 0 and 17".into()),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err(format!("Unexpected arguments for notation '{name}'"));
+        }
+        let required = |digits: Option<usize>| {
+            digits.ok_or_else(|| format!("Notation '{name}' expects a number of digits"))
+        };
+        match name {
+            "auto" => Ok(Notation::Auto),
+            "fixed" => Ok(Notation::Fixed(required(digits)?)),
+            "sig" => match required(digits)? {
+                0 => Err("Expected at least one significant digit".into()),
+                digits => Ok(Notation::Significant(digits)),
+            },
+            "sci" => Ok(Notation::Scientific(digits)),
+            "eng" => Ok(Notation::Engineering(digits)),
+            "hex" => Ok(Notation::Hex),
+            "bin" => Ok(Notation::Binary),
+            other => Err(format!(
+                "Unknown notation '{other}', expected 'auto', 'fixed', 'sig', 'sci', 'eng', 'hex' or 'bin'"
+            )),
+        }
+    }
+}
+
+/// Number formatting used when results are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NumberFormat {
+    pub notation: Notation,
+    /// Groups the digits of the integer part in thousands, e.g. `1,234,567`.
+    pub separators: bool,
+}
+
+impl NumberFormat {
+    pub fn format(&self, number: f64) -> String {
+        if !number.is_finite() {
+            return number.to_string();
+        }
+        let text = match self.notation {
+            Notation::Auto => number.to_string(),
+            Notation::Fixed(places) => format!("{number:.places$}"),
+            Notation::Significant(digits) => significant(number, digits),
+            Notation::Scientific(Some(places)) => format!("{number:.places$e}"),
+            Notation::Scientific(None) => format!("{number:e}"),
+            Notation::Engineering(places) => engineering(number, places),
+            Notation::Hex | Notation::Binary => match integer(number) {
+                Some(int) if self.notation == Notation::Hex => {
+                    return format!("{}0x{:x}", sign(int), int.unsigned_abs());
+                }
+                Some(int) => return format!("{}0b{:b}", sign(int), int.unsigned_abs()),
+                None => number.to_string(),
+            },
+        };
+        self.grouped(text)
+    }
+
+    /// Decimals keep their own digits; only separators apply to them.
+    pub fn format_decimal(&self, decimal: Decimal) -> String {
+        self.grouped(decimal.to_string())
+    }
+
+    /// Formats the numbers of `value`, including list items and the parts of
+    /// complex numbers, quantities and intervals. Other values use their
+    /// default representation.
+    pub fn format_value(&self, value: &Value) -> String {
+        let number = |number| self.format(number);
+        match value {
+            Value::Number(number) => self.format(*number),
+            Value::Decimal(decimal) => self.format_decimal(*decimal),
+            Value::Complex(complex) => complex.format_with(number),
+            Value::Quantity(quantity) => quantity.format_with(number),
+            Value::Interval(interval) => interval.format_with(number),
+            Value::List(items) => {
+                let items: Vec<_> = items.iter().map(|item| self.format_value(item)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            value => value.to_string(),
+        }
+    }
+
+    fn grouped(&self, text: String) -> String {
+        if self.separators {
+            group_thousands(&text)
+        } else {
+            text
+        }
+    }
+}
+
+fn sign(int: i64) -> &'static str {
+    if int < 0 { "-" } else { "" }
+}
+
+fn integer(number: f64) -> Option<i64> {
+    (number.fract() == 0. && number.abs() < 2f64.powi(63)).then_some(number as i64)
+}
+
+fn significant(number: f64, digits: usize) -> String {
+    // Rounding in scientific notation keeps exactly `digits` digits
+    let rounded: f64 = format!("{number:.*e}", digits - 1)
+        .parse()
+        .unwrap_or(number);
+    rounded.to_string()
+}
+
+fn engineering(number: f64, places: Option<usize>) -> String {
+    if number == 0. {
+        return "0e0".to_string();
+    }
+    let mut exponent = number.abs().log10().floor() as i32;
+    exponent -= exponent.rem_euclid(3);
+    let mut mantissa = number / 10f64.powi(exponent);
+    let mut text = mantissa_text(mantissa, places);
+    // Rounding may carry the mantissa up to 1000
+    if text
+        .trim_start_matches('-')
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .len()
+        > 3
+    {
+        exponent += 3;
+        mantissa /= 1000.;
+        text = mantissa_text(mantissa, places);
+    }
+    format!("{text}e{exponent}")
+}
+
+fn mantissa_text(mantissa: f64, places: Option<usize>) -> String {
+    match places {
+        Some(places) => format!("{mantissa:.places$}"),
+        // Drops the noise of the division by a power of ten
+        None => significant(mantissa, 15),
+    }
+}
+
+/// Inserts commas into the leading run of digits.
+fn group_thousands(text: &str) -> String {
+    let start = text
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let end = text[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(text.len(), |end| start + end);
+    let digits = &text[start..end];
+    let mut grouped = String::with_capacity(text.len() + digits.len() / 3);
+    grouped.push_str(&text[..start]);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(&text[end..]);
+    grouped
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Dual number `re + du·ε` with `ε² = 0`, carrying a value together with its
+/// derivative through forward-mode automatic differentiation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual {
+    pub re: f64,
+    pub du: f64,
+}
+
+impl Dual {
+    pub fn new(re: f64, du: f64) -> Self {
+        Self { re, du }
+    }
+
+    /// The independent variable of a differentiation at `re`.
+    pub fn variable(re: f64) -> Self {
+        Self::new(re, 1.)
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.re.is_finite() && self.du.is_finite()
+    }
+
+    /// Applies a function with the given value and derivative at `self.re`.
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        Self::new(value, derivative * self.du)
+    }
+
+    pub fn abs(self) -> Self {
+        let sign = if self.re == 0. { 0. } else { self.re.signum() };
+        self.chain(self.re.abs(), sign)
+    }
+
+    pub fn sqrt(self) -> Self {
+        let root = self.re.sqrt();
+        self.chain(root, 0.5 / root)
+    }
+
+    pub fn exp(self) -> Self {
+        let exp = self.re.exp();
+        self.chain(exp, exp)
+    }
+
+    pub fn ln(self) -> Self {
+        self.chain(self.re.ln(), 1. / self.re)
+    }
+
+    pub fn sin(self) -> Self {
+        self.chain(self.re.sin(), self.re.cos())
+    }
+
+    pub fn cos(self) -> Self {
+        self.chain(self.re.cos(), -self.re.sin())
+    }
+}
+
+impl From<f64> for Dual {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.)
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.du + rhs.du)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.du - rhs.du)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.re * rhs.re, self.du * rhs.re + self.re * rhs.du)
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self::new(
+            self.re / rhs.re,
+            (self.du * rhs.re - self.re * rhs.du) / (rhs.re * rhs.re),
+        )
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.du)
+    }
+}
+
+impl fmt::Display for Dual {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.du < 0. {
+            write!(f, "{}-{}ε", self.re, -self.du)
+        } else {
+            write!(f, "{}+{}ε", self.re, self.du)
+        }
+    }
+}
@@ -1,12 +1,41 @@
+use std::cell::RefCell;
 use std::fmt;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::slice;
 use std::{collections::HashMap, rc::Rc};
 
+use super::complex::Complex;
+use super::decimal::{Decimal, DecimalMode, MAX_SCALE};
+use super::display::NumberFormat;
+use super::dual::Dual;
+use super::interval::Interval;
+use super::numeric::{NumericError, bisect, golden_section, newton, simpson};
+use super::parser::{Operators, Program, program_with, script_with};
+use super::random::Rng;
+use super::stats;
+use super::symbolic::{DeriveError, Sym};
 use super::tokens::{
-    Expression, FuncAssign, FuncCall, IBinaryOperation, IExpression, IUnaryOperation, Ident, Number,
+    BinaryOperation, Block, Clause, Expression, ForIter, FuncAssign, FuncCall, IBinaryOperation,
+    IClause, IExpression, IFuncCall, IFuncCallArgs, IIdent, IPiecewise, IUnaryOperation, Ident,
+    Number, Param, Piecewise, Token, UnaryOperation, UnitExpr, VarAssign, VarAssignExpr,
 };
+use super::types::{self, Span};
+use super::units::{Dimension, Quantity, Unit, default_units};
+use super::value::{ArithmeticError, Value, format_template};
 
 #[macro_export]
 macro_rules! builtin_func {
+    ($name:ident, $min_argc:literal.., $closure:expr) => {
+        (
+            stringify!($name).into(),
+            Func::Variadic {
+                inner: Rc::new($closure),
+                min_argc: $min_argc,
+            },
+        )
+    };
     ($name:ident, $argc:expr, $closure:expr) => {
         (
             stringify!($name).into(),
@@ -18,28 +47,654 @@ macro_rules! builtin_func {
     };
 }
 
+#[macro_export]
+macro_rules! intrinsic_func {
+    ($name:ident, $argc:expr, $closure:expr) => {
+        (
+            stringify!($name).into(),
+            Func::Intrinsic {
+                inner: Rc::new($closure),
+                argc: $argc,
+            },
+        )
+    };
+}
+
+pub type BuiltinFn = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+/// Builtin with access to the evaluation context, for functions whose
+/// behaviour depends on its settings or which call back into the interpreter.
+pub type IntrinsicFn<'a> =
+    Rc<dyn Fn(&Context<'a>, &FuncCall<'a>, &[Value]) -> Result<Value, EvaluateExpressionError<'a>>>;
+
 #[derive(Clone)]
 pub enum Func<'a> {
     Builtin {
-        inner: Rc<dyn Fn(&[f64]) -> Result<f64, String>>,
+        inner: BuiltinFn,
         argc: usize,
     },
+    Variadic {
+        inner: BuiltinFn,
+        min_argc: usize,
+    },
+    Intrinsic {
+        inner: IntrinsicFn<'a>,
+        argc: RangeInclusive<usize>,
+    },
     Custom(FuncAssign<'a>),
+    Piecewise(Piecewise<'a>),
+    /// A user function of an imported module, whose body calls the
+    /// functions of `namespace` by their unqualified names.
+    Imported {
+        namespace: Rc<str>,
+        func: Box<Func<'a>>,
+    },
+}
+
+impl Func<'_> {
+    /// Numbers of arguments the function accepts.
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        match self {
+            Func::Builtin { argc, .. } => *argc..=*argc,
+            Func::Variadic { min_argc, .. } => *min_argc..=usize::MAX,
+            Func::Intrinsic { argc, .. } => argc.clone(),
+            Func::Custom(custom_func) => {
+                let argc = custom_func.data.args.data.0.len();
+                argc..=argc
+            }
+            Func::Piecewise(func) => func.data.argc()..=func.data.argc(),
+            Func::Imported { func, .. } => func.arity(),
+        }
+    }
+}
+
+/// Cached results of `memo` functions, keyed by function name, arguments
+/// and the modes they were evaluated in. Defining any function clears the
+/// results, since they may depend on it.
+#[derive(Debug, Default)]
+pub struct Memo {
+    results: HashMap<MemoKey, Value>,
+    /// Calls answered from the cache.
+    pub hits: u64,
+    /// Calls that had to be evaluated.
+    pub misses: u64,
+}
+
+/// Call of a `memo` function in the strict, complex, interval and decimal
+/// modes of the context, which all change what it evaluates to.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct MemoKey {
+    name: String,
+    args: Vec<MemoArg>,
+    modes: (bool, bool, bool, Option<DecimalMode>),
+}
+
+/// Argument of a memoized call. Floats are compared by their bits, so that
+/// they can be hashed.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum MemoArg {
+    Number(u64),
+    Decimal(i128, u32),
+    Bool(bool),
+    Str(Rc<str>),
+    Complex(u64, u64),
+    Quantity(u64, Dimension, Option<(Rc<str>, u64)>),
+    Interval(u64, u64),
+    Dual(u64, u64),
+    List(Vec<MemoArg>),
+    Func(Rc<str>),
+}
+
+impl From<&Value> for MemoArg {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Number(number) => MemoArg::Number(number.to_bits()),
+            Value::Decimal(decimal) => MemoArg::Decimal(decimal.units, decimal.scale),
+            Value::Bool(value) => MemoArg::Bool(*value),
+            Value::Str(value) => MemoArg::Str(value.clone()),
+            Value::Complex(complex) => MemoArg::Complex(complex.re.to_bits(), complex.im.to_bits()),
+            Value::Quantity(quantity) => MemoArg::Quantity(
+                quantity.value.to_bits(),
+                quantity.dim,
+                quantity
+                    .unit
+                    .as_ref()
+                    .map(|(name, scale)| (name.clone(), scale.to_bits())),
+            ),
+            Value::Interval(interval) => {
+                MemoArg::Interval(interval.lo.to_bits(), interval.hi.to_bits())
+            }
+            Value::Dual(dual) => MemoArg::Dual(dual.re.to_bits(), dual.du.to_bits()),
+            Value::List(items) => MemoArg::List(items.iter().map(MemoArg::from).collect()),
+            Value::Func(name) => MemoArg::Func(name.clone()),
+        }
+    }
+}
+
+impl Memo {
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// Drops the cached results and resets the statistics.
+    pub fn clear(&mut self) {
+        *self = Memo::default();
+    }
+
+    /// Key of a call of `func` in `ctx`, if it is a `memo` function.
+    fn key(ctx: &Context, func: &Func, args: &[Value]) -> Option<MemoKey> {
+        let name = match func {
+            Func::Custom(func) if func.data.memo => func.data.ident.data.0.clone(),
+            Func::Piecewise(func) if func.data.memo => func.data.ident.data.0.clone(),
+            Func::Imported { namespace, func } => {
+                let mut key = Memo::key(ctx, func, args)?;
+                key.name = format!("{namespace}.{}", key.name);
+                return Some(key);
+            }
+            _ => return None,
+        };
+        Some(MemoKey {
+            name,
+            args: args.iter().map(MemoArg::from).collect(),
+            modes: (ctx.strict, ctx.complex, ctx.interval, ctx.decimal),
+        })
+    }
+
+    fn get(&mut self, key: &MemoKey) -> Option<Value> {
+        let value = self.results.get(key).cloned();
+        match value {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        value
+    }
+}
+
+/// Result of a function body: a value, or the call it ends in.
+enum Tail<'f, 'a> {
+    Value(Value),
+    Call(&'f Func<'a>, FuncCall<'a>, Vec<Value>),
+}
+
+/// Variables visible to an expression.
+#[derive(Clone)]
+struct Scope<'s> {
+    /// Function arguments and `let` bindings.
+    locals: HashMap<String, Value>,
+    /// Variables of the context, which function bodies do not see.
+    globals: Option<&'s HashMap<String, Value>>,
+    /// Namespace of the imported function being evaluated.
+    namespace: Option<&'s str>,
+}
+
+impl<'s> Scope<'s> {
+    fn global(vars: &'s HashMap<String, Value>) -> Self {
+        Self {
+            locals: HashMap::new(),
+            globals: Some(vars),
+            namespace: None,
+        }
+    }
+
+    fn function(locals: HashMap<String, Value>, namespace: Option<&'s str>) -> Self {
+        Self {
+            locals,
+            globals: None,
+            namespace,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.locals
+            .get(name)
+            .or_else(|| self.globals.and_then(|globals| globals.get(name)))
+    }
 }
 
 #[derive(Default)]
 pub struct Context<'a> {
-    pub vars: HashMap<String, f64>,
+    pub vars: HashMap<String, Value>,
     pub funcs: HashMap<String, Func<'a>>,
+    /// Keeps booleans apart from numbers: comparisons produce `true`/`false`,
+    /// and mixing booleans with arithmetic raises `TypeMismatch`.
+    pub strict: bool,
+    /// Lets `sqrt` and `ln` of negative numbers produce complex results.
+    pub complex: bool,
+    pub units: HashMap<String, Unit>,
+    /// Evaluates every number as an interval that encloses the exact result.
+    pub interval: bool,
+    /// Evaluates numbers as base-10 decimals, rounding results to a fixed scale.
+    pub decimal: Option<DecimalMode>,
+    /// Generator behind the random builtins, seeded from the clock unless
+    /// [`Context::seed`] is called.
+    pub rng: RefCell<Rng>,
+    /// How `print` and the REPL write numbers.
+    pub format: NumberFormat,
+    /// Results of calls of `memo` functions.
+    pub memo: RefCell<Memo>,
+    /// Names of constants with the source of the statements defining them,
+    /// or `None` for those defined through [`Context::define_const`].
+    consts: HashMap<String, Option<Span<'a>>>,
+    /// Files being imported, innermost last, which relative imports start
+    /// from and which must not import themselves again.
+    importing: Vec<PathBuf>,
+    /// Sources of imported files by canonical path, shared with the modules.
+    /// Definitions point into them, so each is leaked once and reused while
+    /// the file stays the same.
+    sources: Rc<RefCell<HashMap<PathBuf, &'a str>>>,
+    /// Sources run by [`Context::prelude`], which modules load as well.
+    preludes: Vec<(PathBuf, &'a str)>,
+    /// Variables defined by the preludes, with their values.
+    prelude_vars: HashMap<String, Value>,
+    /// Infix operators defined by the sources parsed through
+    /// [`Context::script`] and [`Context::program`].
+    operators: Operators,
+    /// Steps a single [`Context::execute`] may take, counting every statement
+    /// and loop iteration. Unlimited if `None`.
+    pub fuel: Option<u64>,
 }
 
+/// Functions written in nelang that [`Context::new`] defines.
+pub const PRELUDE: &str = include_str!("prelude.ne");
+
 impl<'a> Context<'a> {
     pub fn new() -> Self {
+        let mut this = Context::bare();
+        this.prelude(Path::new("prelude.ne"), PRELUDE)
+            .expect("The prelude is valid");
+        this
+    }
+
+    /// Context with the builtins but without the prelude.
+    pub fn bare() -> Self {
         let mut this = Context::default();
-        this.funcs.extend([builtin_func!(print, 1, |args| {
-            println!("{}", args[0]);
-            Ok(0.)
-        })]);
+        for (name, unit) in default_units() {
+            this.define_unit(name, unit);
+        }
+        this.funcs.extend([
+            intrinsic_func!(print, 1..=1, |ctx, _, args| {
+                println!("{}", ctx.display(&args[0]));
+                Ok(Value::Number(0.))
+            }),
+            intrinsic_func!(assert, 1..=2, |ctx, call, args| {
+                let holds = match &args[0] {
+                    Value::Bool(value) => *value,
+                    value if ctx.strict => {
+                        return Err(EvaluateExpressionError::TypeMismatch(
+                            call.data.args.data.0[0].clone(),
+                            "bool",
+                            value.type_name(),
+                        ));
+                    }
+                    value => value.is_truthy(),
+                };
+                let message = match args.get(1) {
+                    Some(message) => Some(message.as_str().map_err(|err| {
+                        EvaluateExpressionError::BuiltinFunctionError(call.clone(), err)
+                    })?),
+                    None => None,
+                };
+                if !holds {
+                    return Err(EvaluateExpressionError::AssertionFailed(
+                        call.clone(),
+                        message.map(str::to_string),
+                    ));
+                }
+                Ok(ctx.truth(true))
+            }),
+            intrinsic_func!(str, 1..=1, |ctx, _, args| Ok(ctx.display(&args[0]).into())),
+            builtin_func!(len, 1, |args| {
+                Ok(Value::Number(args[0].as_str()?.chars().count() as f64))
+            }),
+            intrinsic_func!(format, 1..=usize::MAX, |ctx, call, args| {
+                let text = args[0]
+                    .as_str()
+                    .and_then(|template| {
+                        format_template(template, &args[1..], |value| ctx.display(value))
+                    })
+                    .map_err(|err| {
+                        EvaluateExpressionError::BuiltinFunctionError(call.clone(), err)
+                    })?;
+                Ok(text.into())
+            }),
+            builtin_func!(count, 0.., |args| Ok(Value::Number(
+                samples(args)?.len() as f64
+            ))),
+            builtin_func!(mean, 0.., |args| {
+                if let Some(values) = interval_samples(args)? {
+                    let sum = values
+                        .iter()
+                        .fold(Interval::point(0.), |sum, &value| sum + value);
+                    let count = Interval::point(values.len() as f64);
+                    return Ok(Value::Interval(sum.checked_div(count).unwrap()));
+                }
+                Ok(Value::Number(stats::mean(&nonempty_samples(args)?)))
+            }),
+            builtin_func!(median, 0.., |args| Ok(Value::Number(stats::median(
+                &nonempty_samples(args)?
+            )))),
+            builtin_func!(variance, 0.., |args| Ok(Value::Number(stats::variance(
+                &nonempty_samples(args)?
+            )))),
+            builtin_func!(stddev, 0.., |args| Ok(Value::Number(
+                stats::variance(&nonempty_samples(args)?).sqrt()
+            ))),
+            builtin_func!(min, 0.., |args| {
+                if let Some(values) = interval_samples(args)? {
+                    return Ok(Value::Interval(
+                        values.into_iter().reduce(Interval::min).unwrap(),
+                    ));
+                }
+                Ok(Value::Number(
+                    nonempty_samples(args)?
+                        .into_iter()
+                        .fold(f64::INFINITY, f64::min),
+                ))
+            }),
+            builtin_func!(max, 0.., |args| {
+                if let Some(values) = interval_samples(args)? {
+                    return Ok(Value::Interval(
+                        values.into_iter().reduce(Interval::max).unwrap(),
+                    ));
+                }
+                Ok(Value::Number(
+                    nonempty_samples(args)?
+                        .into_iter()
+                        .fold(f64::NEG_INFINITY, f64::max),
+                ))
+            }),
+            builtin_func!(percentile, 1.., |args| {
+                let p = args[0].as_number()?;
+                if !(0. ..=100.).contains(&p) {
+                    return Err("Percentile must be between 0 and 100".to_string());
+                }
+                Ok(Value::Number(stats::percentile(
+                    &nonempty_samples(&args[1..])?,
+                    p,
+                )))
+            }),
+            builtin_func!(histogram, 1.., |args| {
+                let bins = args[0].as_number()?;
+                if !(1. ..=stats::MAX_BINS as f64).contains(&bins) || bins.fract() != 0. {
+                    return Err(format!(
+                        "Number of bins must be an integer between 1 and {}",
+                        stats::MAX_BINS
+                    ));
+                }
+                let counts = stats::histogram(&nonempty_samples(&args[1..])?, bins as usize);
+                Ok(Value::List(
+                    counts
+                        .into_iter()
+                        .map(|count| Value::Number(count as f64))
+                        .collect(),
+                ))
+            }),
+            intrinsic_func!(rand, 0..=0, |ctx, _, _| {
+                Ok(Value::Number(ctx.rng.borrow_mut().next_f64()))
+            }),
+            intrinsic_func!(randint, 2..=2, |ctx, call, args| {
+                let error = |err: &str| {
+                    EvaluateExpressionError::BuiltinFunctionError(call.clone(), err.into())
+                };
+                let (lo, hi) = (real_arg(call, &args[0])?, real_arg(call, &args[1])?);
+                if lo.fract() != 0.
+                    || hi.fract() != 0.
+                    || lo.abs() > 2f64.powi(53)
+                    || hi.abs() > 2f64.powi(53)
+                {
+                    return Err(error("Bounds must be integers"));
+                }
+                if lo > hi {
+                    return Err(error("Lower bound exceeds upper bound"));
+                }
+                let offset = ctx.rng.borrow_mut().below((hi - lo) as u64 + 1);
+                Ok(Value::Number(lo + offset as f64))
+            }),
+            intrinsic_func!(normal, 2..=2, |ctx, call, args| {
+                let (mu, sigma) = (real_arg(call, &args[0])?, real_arg(call, &args[1])?);
+                if sigma.is_nan() || sigma < 0. {
+                    return Err(EvaluateExpressionError::BuiltinFunctionError(
+                        call.clone(),
+                        "Standard deviation must not be negative".to_string(),
+                    ));
+                }
+                Ok(Value::Number(ctx.rng.borrow_mut().normal(mu, sigma)))
+            }),
+            intrinsic_func!(choice, 0..=usize::MAX, |ctx, call, args| {
+                let options = match args {
+                    [Value::List(items)] => items.as_ref(),
+                    args => args,
+                };
+                if options.is_empty() {
+                    return Err(EvaluateExpressionError::BuiltinFunctionError(
+                        call.clone(),
+                        "Expected at least one value".to_string(),
+                    ));
+                }
+                let index = ctx.rng.borrow_mut().below(options.len() as u64);
+                Ok(options[index as usize].clone())
+            }),
+            intrinsic_func!(round, 1..=2, |ctx, call, args| {
+                let error = |err: &str| {
+                    EvaluateExpressionError::BuiltinFunctionError(call.clone(), err.into())
+                };
+                let places = match args.get(1) {
+                    // Places are exact in interval mode too
+                    Some(Value::Interval(places)) if places.is_point() => Some(places.lo),
+                    Some(arg) => Some(real_arg(call, arg)?),
+                    None => None,
+                };
+                let places = match places {
+                    Some(places)
+                        if (0. ..=MAX_SCALE as f64).contains(&places) && places.fract() == 0. =>
+                    {
+                        places as u32
+                    }
+                    Some(_) => {
+                        return Err(error(
+                            "Number of places must be an integer between 0 and 28",
+                        ));
+                    }
+                    None => 0,
+                };
+                let rounding = ctx.decimal.map(|mode| mode.rounding).unwrap_or_default();
+                let round = |decimal: Option<Decimal>| {
+                    decimal
+                        .and_then(|decimal| decimal.round(places, rounding))
+                        .ok_or_else(|| error("Number is out of the decimal range"))
+                };
+                let decimal = match &args[0] {
+                    Value::Decimal(decimal) => Some(*decimal),
+                    Value::Interval(interval) => {
+                        // Rounding is monotone, so the bounds round to bounds
+                        let lo = round(Decimal::from_f64(interval.lo))?;
+                        let hi = round(Decimal::from_f64(interval.hi))?;
+                        return Ok(Value::Interval(Interval {
+                            lo: Interval::enclose_literal(&lo.to_string(), lo.to_f64()).lo,
+                            hi: Interval::enclose_literal(&hi.to_string(), hi.to_f64()).hi,
+                        }));
+                    }
+                    other => Decimal::from_f64(real_arg(call, other)?),
+                };
+                let rounded = round(decimal)?;
+                Ok(if ctx.decimal.is_some() {
+                    Value::Decimal(rounded)
+                } else {
+                    Value::Number(rounded.to_f64())
+                })
+            }),
+            builtin_func!(abs, 1, |args| match &args[0] {
+                Value::Complex(complex) => Ok(Value::Number(complex.abs())),
+                Value::Interval(interval) => Ok(Value::Interval(interval.abs())),
+                Value::Dual(dual) => Ok(Value::Dual(dual.abs())),
+                Value::Quantity(quantity) => Ok(Value::Quantity(Quantity {
+                    value: quantity.value.abs(),
+                    ..quantity.clone()
+                })),
+                other => Ok(Value::Number(other.as_number()?.abs())),
+            }),
+            builtin_func!(re, 1, |args| Ok(Value::Number(args[0].as_complex()?.re))),
+            builtin_func!(im, 1, |args| Ok(Value::Number(args[0].as_complex()?.im))),
+            builtin_func!(conj, 1, |args| Ok(Value::complex(
+                args[0].as_complex()?.conj()
+            ))),
+            builtin_func!(arg, 1, |args| Ok(Value::Number(
+                args[0].as_complex()?.arg()
+            ))),
+            builtin_func!(exp, 1, |args| match &args[0] {
+                Value::Complex(complex) => Ok(Value::complex(complex.exp())),
+                Value::Interval(interval) => Ok(Value::Interval(interval.exp())),
+                Value::Dual(dual) => Ok(Value::Dual(dual.exp())),
+                other => Ok(Value::Number(other.as_number()?.exp())),
+            }),
+            builtin_func!(sin, 1, |args| match &args[0] {
+                Value::Interval(interval) => Ok(Value::Interval(interval.sin())),
+                Value::Dual(dual) => Ok(Value::Dual(dual.sin())),
+                other => Ok(Value::Number(other.as_number()?.sin())),
+            }),
+            builtin_func!(cos, 1, |args| match &args[0] {
+                Value::Interval(interval) => Ok(Value::Interval(interval.cos())),
+                Value::Dual(dual) => Ok(Value::Dual(dual.cos())),
+                other => Ok(Value::Number(other.as_number()?.cos())),
+            }),
+            intrinsic_func!(sqrt, 1..=1, |ctx, call, args| {
+                match &args[0] {
+                    Value::Complex(complex) => Ok(Value::complex(complex.sqrt())),
+                    Value::Interval(interval) => interval
+                        .sqrt()
+                        .map(Value::Interval)
+                        .ok_or("Square root of a negative number".to_string()),
+                    Value::Dual(dual) if dual.re < 0. => {
+                        Err("Square root of a negative number".to_string())
+                    }
+                    Value::Dual(dual) => Ok(Value::Dual(dual.sqrt())),
+                    Value::Quantity(quantity) if quantity.value < 0. => {
+                        Err("Square root of a negative number".to_string())
+                    }
+                    Value::Quantity(quantity) => match quantity.dim.sqrt() {
+                        Some(dim) => Ok(Value::quantity(Quantity::new(quantity.value.sqrt(), dim))),
+                        None => Err(format!("Cannot take square root of {}", quantity.dim)),
+                    },
+                    other => match other.as_number() {
+                        Ok(number) if number >= 0. => Ok(Value::Number(number.sqrt())),
+                        Ok(number) if ctx.complex => {
+                            Ok(Value::complex(Complex::from(number).sqrt()))
+                        }
+                        Ok(_) => Err("Square root of a negative number".to_string()),
+                        Err(err) => Err(err),
+                    },
+                }
+                .map_err(|err| EvaluateExpressionError::BuiltinFunctionError(call.clone(), err))
+            }),
+            intrinsic_func!(ln, 1..=1, |ctx, call, args| {
+                match &args[0] {
+                    Value::Complex(complex) if !complex.is_zero() => {
+                        Ok(Value::complex(complex.ln()))
+                    }
+                    Value::Interval(interval) => interval
+                        .ln()
+                        .map(Value::Interval)
+                        .ok_or("Logarithm of a non-positive number".to_string()),
+                    Value::Dual(dual) if dual.re <= 0. => {
+                        Err("Logarithm of a non-positive number".to_string())
+                    }
+                    Value::Dual(dual) => Ok(Value::Dual(dual.ln())),
+                    other => match other.as_number() {
+                        Ok(number) if number > 0. => Ok(Value::Number(number.ln())),
+                        Ok(number) if number < 0. && ctx.complex => {
+                            Ok(Value::complex(Complex::from(number).ln()))
+                        }
+                        Ok(_) => Err("Logarithm of a non-positive number".to_string()),
+                        Err(err) => Err(err),
+                    },
+                }
+                .map_err(|err| EvaluateExpressionError::BuiltinFunctionError(call.clone(), err))
+            }),
+            builtin_func!(interval, 2, |args| {
+                Interval::new(args[0].as_interval()?.lo, args[1].as_interval()?.hi)
+                    .map(Value::Interval)
+                    .ok_or("Lower bound exceeds upper bound".to_string())
+            }),
+            builtin_func!(lo, 1, |args| Ok(Value::Number(args[0].as_interval()?.lo))),
+            builtin_func!(hi, 1, |args| Ok(Value::Number(args[0].as_interval()?.hi))),
+            builtin_func!(width, 1, |args| Ok(Value::Number(
+                args[0].as_interval()?.width()
+            ))),
+            intrinsic_func!(to, 2..=2, |_, call, args| {
+                let error = |err| EvaluateExpressionError::BuiltinFunctionError(call.clone(), err);
+                let value = args[0].as_quantity().map_err(error)?;
+                let target = args[1].as_quantity().map_err(error)?;
+                if value.dim != target.dim {
+                    return Err(EvaluateExpressionError::DimensionMismatch(
+                        call.data.args.data.0[0].clone(),
+                        value.dim,
+                        target.dim,
+                    ));
+                }
+                if target.value == 0. {
+                    return Err(error("Cannot convert to a zero unit".to_string()));
+                }
+                let name = call.data.args.data.0[1].pos.fragment();
+                Ok(Value::quantity(Quantity {
+                    unit: Some(((*name).into(), target.value)),
+                    ..value
+                }))
+            }),
+            intrinsic_func!(diff, 2..=2, |ctx, call, args| {
+                let x = args[1].as_number().map_err(|err| {
+                    EvaluateExpressionError::BuiltinFunctionError(call.clone(), err)
+                })?;
+                ctx.partial(&args[0], call, &[x], 0).map(Value::Number)
+            }),
+            intrinsic_func!(grad, 2..=usize::MAX, |ctx, call, args| {
+                let point = args[1..]
+                    .iter()
+                    .map(Value::as_number)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| {
+                        EvaluateExpressionError::BuiltinFunctionError(call.clone(), err)
+                    })?;
+                (0..point.len())
+                    .map(|index| {
+                        ctx.partial(&args[0], call, &point, index)
+                            .map(Value::Number)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|partials| Value::List(partials.into()))
+            }),
+            intrinsic_func!(solve, 3..=5, |ctx, call, args| {
+                let (lo, hi) = (real_arg(call, &args[1])?, real_arg(call, &args[2])?);
+                let (tol, max_iter) = solver_options(call, &args[3..], 1e-10, 200)?;
+                bisect(|x| ctx.sample(&args[0], call, x), lo, hi, tol, max_iter)
+                    .map(Value::Number)
+                    .map_err(|err| numeric_error(call, err))
+            }),
+            intrinsic_func!(newton, 2..=4, |ctx, call, args| {
+                let x0 = real_arg(call, &args[1])?;
+                let (tol, max_iter) = solver_options(call, &args[2..], 1e-10, 100)?;
+                newton(|x| ctx.sample_dual(&args[0], call, x), x0, tol, max_iter)
+                    .map(Value::Number)
+                    .map_err(|err| numeric_error(call, err))
+            }),
+            intrinsic_func!(integrate, 3..=5, |ctx, call, args| {
+                let (a, b) = (real_arg(call, &args[1])?, real_arg(call, &args[2])?);
+                let (tol, max_depth) = solver_options(call, &args[3..], 1e-10, 50)?;
+                simpson(|x| ctx.sample(&args[0], call, x), a, b, tol, max_depth)
+                    .map(Value::Number)
+                    .map_err(|err| numeric_error(call, err))
+            }),
+            intrinsic_func!(minimize, 3..=5, |ctx, call, args| {
+                let (lo, hi) = (real_arg(call, &args[1])?, real_arg(call, &args[2])?);
+                let (tol, max_iter) = solver_options(call, &args[3..], 1e-10, 200)?;
+                golden_section(|x| ctx.sample(&args[0], call, x), lo, hi, tol, max_iter)
+                    .map(Value::Number)
+                    .map_err(|err| numeric_error(call, err))
+            }),
+        ]);
         this
     }
 }
@@ -49,8 +704,26 @@ pub enum EvaluateExpressionError<'a> {
     InvalidFunctionArgc(FuncCall<'a>, usize),
     UndefinedFunction(FuncCall<'a>),
     UndefinedVar(Ident<'a>),
+    /// A variable that is not an argument or `let` binding of the function
+    /// body it is used in.
+    UndefinedLocalVar(Ident<'a>),
     DivisionByZero(Expression<'a>),
     Overflow(Expression<'a>),
+    InvalidOperands(Expression<'a>),
+    TypeMismatch(Expression<'a>, &'static str, &'static str),
+    DimensionMismatch(Expression<'a>, Dimension, Dimension),
+    UndefinedUnit(Ident<'a>),
+    InvalidInterval(Expression<'a>),
+    IndeterminateComparison(Expression<'a>),
+    NoBracket(FuncCall<'a>),
+    NoConvergence(FuncCall<'a>, usize),
+    /// A call of a piecewise function that no clause matches.
+    NoMatchingClause(FuncCall<'a>, Vec<Value>),
+    /// An assignment to a constant, with the source of the definition of the
+    /// constant if it was made by a script.
+    ConstReassignment(Ident<'a>, Option<Span<'a>>),
+    /// A call of `assert` with a false condition, and its message if any.
+    AssertionFailed(FuncCall<'a>, Option<String>),
     BuiltinFunctionError(FuncCall<'a>, String),
 }
 
@@ -72,6 +745,13 @@ impl<'a> fmt::Display for EvaluateExpressionError<'a> {
             EvaluateExpressionError::UndefinedVar(ident) => {
                 write!(f, "Undefined variable: '{}'", ident)
             }
+            EvaluateExpressionError::UndefinedLocalVar(ident) => {
+                write!(
+                    f,
+                    "Undefined variable: '{}' (function bodies only see their arguments and 'let' bindings)",
+                    ident
+                )
+            }
             EvaluateExpressionError::DivisionByZero(expr) => {
                 write!(
                     f,
@@ -82,6 +762,85 @@ impl<'a> fmt::Display for EvaluateExpressionError<'a> {
             EvaluateExpressionError::Overflow(expr) => {
                 write!(f, "Numeric overflow in expression: '{}'", expr)
             }
+            EvaluateExpressionError::InvalidOperands(expr) => {
+                write!(f, "Unsupported operand types in expression: '{}'", expr)
+            }
+            EvaluateExpressionError::TypeMismatch(expr, expected, found) => {
+                write!(
+                    f,
+                    "Type mismatch in expression '{}': expected {}, got {}",
+                    expr, expected, found
+                )
+            }
+            EvaluateExpressionError::DimensionMismatch(expr, lhs, rhs) => {
+                write!(
+                    f,
+                    "Dimension mismatch in expression '{}': {} vs {}",
+                    expr, lhs, rhs
+                )
+            }
+            EvaluateExpressionError::UndefinedUnit(ident) => {
+                write!(f, "Undefined unit: '{}'", ident)
+            }
+            EvaluateExpressionError::InvalidInterval(expr) => {
+                write!(f, "Lower bound exceeds upper bound in interval: '{}'", expr)
+            }
+            EvaluateExpressionError::IndeterminateComparison(expr) => {
+                write!(
+                    f,
+                    "Comparison of overlapping intervals is undecidable: '{}'",
+                    expr
+                )
+            }
+            EvaluateExpressionError::NoBracket(func_call) => {
+                write!(
+                    f,
+                    "Function values at the interval ends must have opposite signs: '{}'",
+                    func_call
+                )
+            }
+            EvaluateExpressionError::NoConvergence(func_call, steps) => {
+                write!(
+                    f,
+                    "'{}' did not converge within {} steps: '{}'",
+                    func_call.data.ident, steps, func_call
+                )
+            }
+            EvaluateExpressionError::NoMatchingClause(func_call, args) => {
+                let args: Vec<_> = args.iter().map(Value::to_string).collect();
+                write!(
+                    f,
+                    "No clause of '{}' matches the arguments ({}): '{}'",
+                    func_call.data.ident,
+                    args.join(", "),
+                    func_call
+                )
+            }
+            EvaluateExpressionError::ConstReassignment(ident, definition) => {
+                write!(
+                    f,
+                    "Cannot reassign constant '{}' at line {}, column {}",
+                    ident,
+                    ident.pos.location_line(),
+                    ident.pos.get_column()
+                )?;
+                match definition {
+                    Some(definition) => write!(
+                        f,
+                        "; it was defined at line {}, column {}: '{}'",
+                        definition.location_line(),
+                        definition.get_column(),
+                        definition.trim_end()
+                    ),
+                    None => write!(f, "; it is built in"),
+                }
+            }
+            EvaluateExpressionError::AssertionFailed(_, Some(message)) => {
+                write!(f, "Assertion failed: {}", message)
+            }
+            EvaluateExpressionError::AssertionFailed(func_call, None) => {
+                write!(f, "Assertion failed: '{}'", func_call)
+            }
             EvaluateExpressionError::BuiltinFunctionError(func_call, err) => {
                 write!(f, "Error in built-in function '{}': {}", func_call.data.ident, err)
             }
@@ -89,129 +848,1489 @@ impl<'a> fmt::Display for EvaluateExpressionError<'a> {
     }
 }
 
+/// Recognizes `derive(f, x)`, which defines a new function instead of evaluating.
+pub fn derive_call(expr: &Expression) -> Option<(String, String)> {
+    let IExpression::Call(call) = expr.data.as_ref() else {
+        return None;
+    };
+    if call.data.ident.data.0 != "derive" {
+        return None;
+    }
+    match call.data.args.data.0.as_slice() {
+        [func, var] => match (func.data.as_ref(), var.data.as_ref()) {
+            (IExpression::Ident(func), IExpression::Ident(var)) => {
+                Some((func.data.0.clone(), var.data.0.clone()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum ExecuteError<'a> {
+    Evaluate(EvaluateExpressionError<'a>),
+    Derive(DeriveError),
+    /// `x = ?` inside a statement, where input cannot be read.
+    UserInput(VarAssign<'a>),
+    OutOfFuel(Span<'a>),
+    Import(ImportError),
+}
+
+impl<'a> fmt::Display for ExecuteError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecuteError::Evaluate(err) => write!(f, "{}", err),
+            ExecuteError::Derive(err) => write!(f, "{}", err),
+            ExecuteError::UserInput(token) => {
+                write!(
+                    f,
+                    "Cannot read input for '{}' inside a statement",
+                    token.data.ident
+                )
+            }
+            ExecuteError::OutOfFuel(pos) => {
+                write!(
+                    f,
+                    "Out of fuel at line {}, column {}",
+                    pos.location_line(),
+                    pos.get_column()
+                )
+            }
+            ExecuteError::Import(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Failure to import a module, located in the file it comes from.
+#[derive(Debug)]
+pub struct ImportError {
+    pub path: PathBuf,
+    /// Line and column in the file, if the error comes from its contents.
+    pub location: Option<(u32, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => {
+                write!(
+                    f,
+                    "{}:{}:{}: {}",
+                    self.path.display(),
+                    line,
+                    column,
+                    self.message
+                )
+            }
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl<'a> From<ImportError> for ExecuteError<'a> {
+    fn from(err: ImportError) -> Self {
+        ExecuteError::Import(err)
+    }
+}
+
+impl<'a> From<EvaluateExpressionError<'a>> for ExecuteError<'a> {
+    fn from(err: EvaluateExpressionError<'a>) -> Self {
+        ExecuteError::Evaluate(err)
+    }
+}
+
+impl<'a> From<DeriveError> for ExecuteError<'a> {
+    fn from(err: DeriveError) -> Self {
+        ExecuteError::Derive(err)
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Parses a whole script with the operators defined so far, keeping
+    /// those it defines for later parses.
+    pub fn script(&mut self, input: Span<'a>) -> types::Result<'a, Vec<Program<'a>>> {
+        script_with(input, &mut self.operators)
+    }
+
+    /// Parses a single statement with the operators defined so far, keeping
+    /// those it defines for later parses.
+    pub fn program(&mut self, input: Span<'a>) -> types::Result<'a, Program<'a>> {
+        program_with(input, &mut self.operators)
+    }
+
+    /// Runs a statement, including `if`, `while` and `for` with their blocks,
+    /// within the budget of [`Context::fuel`].
+    pub fn execute(&mut self, program: &Program<'a>) -> Result<(), ExecuteError<'a>> {
+        let mut fuel = self.fuel;
+        self.run(program, &mut fuel)
+    }
+
+    fn run(
+        &mut self,
+        program: &Program<'a>,
+        fuel: &mut Option<u64>,
+    ) -> Result<(), ExecuteError<'a>> {
+        match program {
+            Program::Expression(expr) => {
+                burn(fuel, expr.pos)?;
+                match derive_call(expr) {
+                    Some((name, var)) => {
+                        let func = self.derive(&name, &var)?;
+                        self.funcs
+                            .insert(func.data.ident.data.0.clone(), Func::Custom(func));
+                    }
+                    None => {
+                        self.evaluate_expression(expr)?;
+                    }
+                }
+            }
+            Program::Var(token) => {
+                burn(fuel, token.pos)?;
+                let VarAssignExpr::Expression(expr) = &token.data.expr else {
+                    return Err(ExecuteError::UserInput(token.clone()));
+                };
+                let value = self.evaluate_expression(expr)?;
+                self.assign(token, value)?;
+            }
+            Program::Func(token) => {
+                burn(fuel, token.pos)?;
+                self.define(token.clone());
+            }
+            Program::Piecewise(token) => {
+                burn(fuel, token.pos)?;
+                self.define_piecewise(token.clone());
+            }
+            Program::Comment(_) => {}
+            Program::Import(token) => {
+                burn(fuel, token.pos)?;
+                let path = Path::new(&token.data.path.data.0);
+                let namespace = match &token.data.alias {
+                    Some(alias) => alias.data.0.clone(),
+                    None => namespace_of(path)?,
+                };
+                self.import(path, &namespace)?;
+            }
+            Program::If(token) => {
+                burn(fuel, token.pos)?;
+                let branch = if self.test(&token.data.cond)? {
+                    Some(&token.data.then)
+                } else {
+                    token.data.otherwise.as_ref()
+                };
+                if let Some(block) = branch {
+                    self.run_block(block, fuel)?;
+                }
+            }
+            Program::While(token) => {
+                burn(fuel, token.pos)?;
+                while self.test(&token.data.cond)? {
+                    burn(fuel, token.pos)?;
+                    self.run_block(&token.data.body, fuel)?;
+                }
+            }
+            Program::For(token) => {
+                burn(fuel, token.pos)?;
+                self.writable(&token.data.var)?;
+                let var = &token.data.var.data.0;
+                match &token.data.iter {
+                    ForIter::Range(lo, hi) => {
+                        let (lo, hi) = (self.bound(lo)?, self.bound(hi)?);
+                        let mut i = lo;
+                        while i < hi {
+                            burn(fuel, token.pos)?;
+                            self.vars.insert(var.clone(), self.fixed(Value::Number(i)));
+                            self.run_block(&token.data.body, fuel)?;
+                            i += 1.;
+                        }
+                    }
+                    ForIter::List(expr) => {
+                        let items = match self.evaluate_expression(expr)? {
+                            Value::List(items) => items,
+                            value => {
+                                return Err(EvaluateExpressionError::TypeMismatch(
+                                    expr.clone(),
+                                    "list",
+                                    value.type_name(),
+                                )
+                                .into());
+                            }
+                        };
+                        for item in items.iter() {
+                            burn(fuel, token.pos)?;
+                            self.vars.insert(var.clone(), item.clone());
+                            self.run_block(&token.data.body, fuel)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run_block(
+        &mut self,
+        block: &Block<'a>,
+        fuel: &mut Option<u64>,
+    ) -> Result<(), ExecuteError<'a>> {
+        block
+            .data
+            .0
+            .iter()
+            .try_for_each(|program| self.run(program, fuel))
+    }
+
+    fn test(&self, cond: &Expression<'a>) -> Result<bool, EvaluateExpressionError<'a>> {
+        self.condition(cond, &Scope::global(&self.vars))
+    }
+
+    /// Evaluates an end of a `for` range, which must be a real number.
+    fn bound(&self, expr: &Expression<'a>) -> Result<f64, EvaluateExpressionError<'a>> {
+        let value = self.evaluate_expression(expr)?;
+        value.as_number().map_err(|_| {
+            EvaluateExpressionError::TypeMismatch(expr.clone(), "number", value.type_name())
+        })
+    }
+}
+
+/// Call of the function behind a user-defined operator, e.g. `<+>(a, b)` for
+/// `a <+> b`.
+fn operator_call<'a>(
+    expr: &Expression<'a>,
+    lhs: &Expression<'a>,
+    op: &BinaryOperation<'a>,
+    rhs: &Expression<'a>,
+) -> FuncCall<'a> {
+    Token::new(
+        expr.pos,
+        IFuncCall {
+            ident: Token::new(op.pos, IIdent(op.pos.fragment().to_string())),
+            args: Token::new(expr.pos, IFuncCallArgs(vec![lhs.clone(), rhs.clone()])),
+        },
+    )
+}
+
+/// Namespace named after the stem of the imported file.
+fn namespace_of(path: &Path) -> Result<String, ImportError> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let mut chars = stem.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if valid {
+        Ok(stem.to_string())
+    } else {
+        Err(ImportError {
+            path: path.to_path_buf(),
+            location: None,
+            message: format!("'{stem}' is not a valid namespace, name one with 'as'"),
+        })
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Runs the file at `path` as a module and defines its functions and
+    /// variables in `namespace`, e.g. `geo.area` for `area`. A relative path
+    /// starts from the directory of the importing file.
+    pub fn import(&mut self, path: &Path, namespace: &str) -> Result<(), ImportError> {
+        let path = match self.importing.last().and_then(|file| file.parent()) {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        };
+        let error = |location, message: String| ImportError {
+            path: path.clone(),
+            location,
+            message,
+        };
+        let canonical = fs::canonicalize(&path).map_err(|err| error(None, err.to_string()))?;
+        let importing = self
+            .importing
+            .iter()
+            .position(|file| fs::canonicalize(file).is_ok_and(|file| file == canonical));
+        if let Some(start) = importing {
+            let cycle: Vec<_> = self.importing[start..]
+                .iter()
+                .chain([&path])
+                .map(|file| file.display().to_string())
+                .collect();
+            return Err(error(None, format!("Import cycle: {}", cycle.join(" -> "))));
+        }
+        let source = fs::read_to_string(&path).map_err(|err| error(None, err.to_string()))?;
+        let source = self.source(canonical, source);
+        let mut module = self.module()?;
+        module.importing.push(path.clone());
+        module.run_file(&path, source)?;
+        self.adopt(module, namespace);
+        Ok(())
+    }
+
+    /// The lasting copy of `text`, the contents of the file at `path`.
+    fn source(&self, path: PathBuf, text: String) -> &'a str {
+        let mut sources = self.sources.borrow_mut();
+        match sources.get(&path) {
+            Some(&source) if source == text => source,
+            _ => {
+                let source: &'a str = Box::leak(text.into_boxed_str());
+                sources.insert(path, source);
+                source
+            }
+        }
+    }
+
+    /// Makes the statements to come those of the file at `path`, so that
+    /// relative imports start from its directory.
+    pub fn enter_file(&mut self, path: &Path) {
+        self.importing = vec![path.to_path_buf()];
+    }
+
+    /// Runs `source` like a script, before anything else, so that its
+    /// definitions are available to the statements of this context and of
+    /// the modules it imports. `path` names the source in errors.
+    pub fn prelude(&mut self, path: &Path, source: &'a str) -> Result<(), ImportError> {
+        let vars = self.vars.clone();
+        self.run_file(path, source)?;
+        self.preludes.push((path.to_path_buf(), source));
+        for (name, value) in &self.vars {
+            if vars.get(name) != Some(value) {
+                self.prelude_vars.insert(name.clone(), value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the statements of `source`, locating errors in the file at `path`.
+    fn run_file(&mut self, path: &Path, source: &'a str) -> Result<(), ImportError> {
+        let error = |location, message: String| ImportError {
+            path: path.to_path_buf(),
+            location,
+            message,
+        };
+        let programs = match self.script(Span::new(source)) {
+            Ok((_, programs)) => programs,
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+                let location = (err.input.location_line(), err.input.get_column());
+                return Err(error(Some(location), err.message.into_owned()));
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                return Err(error(None, "Unexpected end of file".to_string()));
+            }
+        };
+        for program in &programs {
+            if let Err(err) = self.execute(program) {
+                // Errors of nested imports already name their own file
+                if let ExecuteError::Import(err) = err {
+                    return Err(err);
+                }
+                let pos = program.pos();
+                let location = (pos.location_line(), pos.get_column());
+                return Err(error(Some(location), err.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Context for a module, with the settings and preludes of this one.
+    fn module(&self) -> Result<Context<'a>, ImportError> {
+        let mut module = Context::bare();
+        module.strict = self.strict;
+        module.complex = self.complex;
+        module.units = self.units.clone();
+        module.interval = self.interval;
+        module.decimal = self.decimal;
+        module.format = self.format;
+        module.fuel = self.fuel;
+        module.importing = self.importing.clone();
+        module.sources = Rc::clone(&self.sources);
+        for &(ref path, source) in &self.preludes {
+            module.prelude(path, source)?;
+        }
+        Ok(module)
+    }
+
+    /// Whether `func` is defined by one of the preludes.
+    fn is_prelude(&self, func: &Func) -> bool {
+        let pos = match func {
+            Func::Custom(func) => func.pos,
+            Func::Piecewise(func) => func.pos,
+            _ => return false,
+        };
+        let start = pos.fragment().as_ptr();
+        self.preludes
+            .iter()
+            .any(|(_, source)| source.as_bytes().as_ptr_range().contains(&start))
+    }
+
+    /// Takes over the definitions of `module` under `namespace`. Builtins and
+    /// the preludes are left out, since the importer has them.
+    fn adopt(&mut self, module: Context<'a>, namespace: &str) {
+        let qualified = |name: &str| format!("{namespace}.{name}");
+        for (name, func) in &module.funcs {
+            if module.is_prelude(func) {
+                continue;
+            }
+            let func = match func.clone() {
+                func @ (Func::Custom(_) | Func::Piecewise(_)) => Func::Imported {
+                    namespace: namespace.into(),
+                    func: Box::new(func),
+                },
+                Func::Imported {
+                    namespace: inner,
+                    func,
+                } => Func::Imported {
+                    namespace: qualified(&inner).into(),
+                    func,
+                },
+                _ => continue,
+            };
+            // Operators cannot be qualified, so they keep their symbol
+            let operator = !name.starts_with(|c: char| c.is_alphabetic() || c == '_');
+            let name = if operator {
+                name.clone()
+            } else {
+                qualified(name)
+            };
+            self.funcs.insert(name, func);
+        }
+        for (name, value) in module.vars {
+            if module.prelude_vars.get(&name) != Some(&value) {
+                self.vars.insert(qualified(&name), value);
+            }
+        }
+        for (name, definition) in module.consts {
+            if !module.prelude_vars.contains_key(&name) {
+                self.consts.insert(qualified(&name), definition);
+            }
+        }
+        self.operators.extend(module.operators);
+        self.memo.get_mut().results.clear();
+    }
+}
+
+/// Takes one step of fuel, failing at `pos` once none is left.
+fn burn<'a>(fuel: &mut Option<u64>, pos: Span<'a>) -> Result<(), ExecuteError<'a>> {
+    match fuel {
+        Some(0) => Err(ExecuteError::OutOfFuel(pos)),
+        Some(steps) => {
+            *steps -= 1;
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
 impl<'a> Context<'a> {
     pub fn evaluate_expression(
         &self,
         expr: &Expression<'a>,
-    ) -> Result<f64, EvaluateExpressionError<'a>> {
-        match expr.data.as_ref() {
-            IExpression::Ident(token) => self
-                .vars
-                .get(token.data.0.as_str())
-                .cloned()
-                .ok_or(EvaluateExpressionError::UndefinedVar(token.clone())),
-            IExpression::Number(number) => match number {
-                Number::Int(token) => Ok(token.data.0 as f64),
-                Number::Float(token) => Ok(token.data.0),
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        self.evaluate(expr, &Scope::global(&self.vars))
+            .map(|value| self.fixed(value))
+    }
+
+    /// In decimal mode, rounds numbers to the configured scale.
+    pub fn fixed(&self, value: Value) -> Value {
+        let Some(mode) = self.decimal else {
+            return value;
+        };
+        let decimal = match &value {
+            Value::Decimal(decimal) => Some(*decimal),
+            Value::Number(number) => Decimal::from_f64(*number),
+            Value::List(items) => {
+                return Value::List(items.iter().map(|item| self.fixed(item.clone())).collect());
+            }
+            _ => None,
+        };
+        match decimal.and_then(|decimal| decimal.round(mode.scale, mode.rounding)) {
+            Some(decimal) => Value::Decimal(decimal),
+            None => value,
+        }
+    }
+
+    /// Text of a result as shown to the user, rounded in decimal mode and
+    /// written with the configured number format.
+    pub fn display(&self, value: &Value) -> String {
+        self.format.format_value(&self.fixed(value.clone()))
+    }
+
+    /// Symbolically derives the user-defined function `name` with respect to its
+    /// argument `var`. The result is named with a prime (`f'`, or `f'x` for
+    /// functions of several arguments); its source is leaked so that it lives as
+    /// long as the context.
+    pub fn derive(&self, name: &str, var: &str) -> Result<FuncAssign<'a>, DeriveError> {
+        let func = match self.funcs.get(name) {
+            Some(Func::Custom(func)) => func,
+            Some(Func::Piecewise(_) | Func::Imported { .. }) => {
+                return Err(DeriveError::Unsupported(name.to_string()));
+            }
+            Some(_) => return Err(DeriveError::NotCustomFunction(name.to_string())),
+            None => return Err(DeriveError::UndefinedFunction(name.to_string())),
+        };
+        let args = &func.data.args.data.0;
+        if !args.iter().any(|arg| arg.data.0 == var) {
+            return Err(DeriveError::UndefinedArgument(
+                name.to_string(),
+                var.to_string(),
+            ));
+        }
+        let derivative = Sym::from_expression(&func.data.expr)?
+            .derivative(var, &self.funcs, &mut vec![name.to_string()])?
+            .simplify();
+        let ident = if args.len() == 1 {
+            format!("{name}'")
+        } else {
+            format!("{name}'{var}")
+        };
+        let source = format!("{ident}{} = {derivative}\n", func.data.args);
+        let source: &'a str = Box::leak(source.into_boxed_str());
+        // The derivative may still apply operators
+        match program_with(Span::new(source), &mut self.operators.clone()) {
+            Ok((_, Program::Func(func))) => Ok(func),
+            _ => Err(DeriveError::Unsupported(source.trim_end().to_string())),
+        }
+    }
+
+    /// Restarts the random builtins from `seed`, making them reproducible.
+    pub fn seed(&mut self, seed: u64) {
+        *self.rng.get_mut() = Rng::new(seed);
+    }
+
+    pub fn define_unit(&mut self, name: &str, unit: Unit) {
+        self.units.insert(name.to_string(), unit);
+    }
+
+    /// Defines a constant, which scripts cannot reassign.
+    pub fn define_const(&mut self, name: &str, value: Value) {
+        self.vars.insert(name.to_string(), value);
+        self.consts.insert(name.to_string(), None);
+    }
+
+    pub fn is_const(&self, name: &str) -> bool {
+        self.consts.contains_key(name)
+    }
+
+    /// Stores the value of an assignment, which must not be to a constant.
+    pub fn assign(
+        &mut self,
+        token: &VarAssign<'a>,
+        value: Value,
+    ) -> Result<(), EvaluateExpressionError<'a>> {
+        self.writable(&token.data.ident)?;
+        let name = &token.data.ident.data.0;
+        if token.data.constant {
+            self.consts.insert(name.clone(), Some(token.pos));
+        }
+        self.vars.insert(name.clone(), value);
+        Ok(())
+    }
+
+    fn writable(&self, ident: &Ident<'a>) -> Result<(), EvaluateExpressionError<'a>> {
+        match self.consts.get(ident.data.0.as_str()) {
+            Some(definition) => Err(EvaluateExpressionError::ConstReassignment(
+                ident.clone(),
+                *definition,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Defines a user function, replacing an earlier definition of the name.
+    /// If that was a piecewise function with clauses left to add, the new
+    /// definition becomes its next clause instead.
+    pub fn define(&mut self, func: FuncAssign<'a>) {
+        let clause = Token::new(
+            func.pos,
+            IClause {
+                params: func
+                    .data
+                    .args
+                    .data
+                    .0
+                    .iter()
+                    .cloned()
+                    .map(Param::Ident)
+                    .collect(),
+                guards: vec![(None, func.data.expr.clone())],
             },
-            IExpression::Unary(hs, op) => {
-                let hr = self.evaluate_expression(hs)?;
-                let r = match *op.data {
-                    IUnaryOperation::Neg => -hr,
-                    IUnaryOperation::Pos => hr,
-                    IUnaryOperation::Not => {
-                        if hr == 0. {
-                            1.
-                        } else {
-                            0.
-                        }
+        );
+        self.memo.get_mut().results.clear();
+        if !self.extend_piecewise(&func.data.ident, [clause]) {
+            self.funcs
+                .insert(func.data.ident.data.0.clone(), Func::Custom(func));
+        }
+    }
+
+    /// Defines a piecewise function, or adds its clauses to one that does not
+    /// cover all arguments yet.
+    pub fn define_piecewise(&mut self, func: Piecewise<'a>) {
+        self.memo.get_mut().results.clear();
+        if !self.extend_piecewise(&func.data.ident, func.data.clauses.iter().cloned()) {
+            self.funcs
+                .insert(func.data.ident.data.0.clone(), Func::Piecewise(func));
+        }
+    }
+
+    fn extend_piecewise(
+        &mut self,
+        ident: &Ident<'a>,
+        clauses: impl IntoIterator<Item = Clause<'a>>,
+    ) -> bool {
+        let mut clauses = clauses.into_iter().peekable();
+        let Some(Func::Piecewise(func)) = self.funcs.get_mut(ident.data.0.as_str()) else {
+            return false;
+        };
+        let argc = clauses.peek().map(|clause| clause.data.params.len());
+        if func.data.is_exhaustive() || argc != Some(func.data.argc()) {
+            return false;
+        }
+        *func = Token::new(
+            func.pos,
+            IPiecewise {
+                ident: func.data.ident.clone(),
+                clauses: func.data.clauses.iter().cloned().chain(clauses).collect(),
+                memo: func.data.memo,
+            },
+        );
+        true
+    }
+
+    /// Binds the arguments to the parameters of `clause`, or returns `None` if
+    /// a literal parameter does not match.
+    fn bind(
+        &self,
+        clause: &IClause<'a>,
+        args: &[Value],
+    ) -> Result<Option<HashMap<String, Value>>, EvaluateExpressionError<'a>> {
+        let mut locals = HashMap::new();
+        for (param, arg) in clause.params.iter().zip(args) {
+            match param {
+                Param::Ident(ident) => {
+                    locals.insert(ident.data.0.clone(), arg.clone());
+                }
+                Param::Literal(expr) => {
+                    let literal = self.evaluate(expr, &Scope::function(HashMap::new(), None))?;
+                    match self.fixed(arg.clone()).equals(&self.fixed(literal)) {
+                        Ok(true) => {}
+                        Ok(false) | Err(ArithmeticError::InvalidOperands) => return Ok(None),
+                        Err(err) => return Err(arithmetic_error(err, expr, expr)),
                     }
-                };
-                if r.is_finite() {
-                    Ok(r)
-                } else {
-                    Err(EvaluateExpressionError::Overflow(expr.clone()))
                 }
             }
-            IExpression::Binary(lhs, op, rhs) => {
-                let lr = self.evaluate_expression(lhs)?;
-                let rr = self.evaluate_expression(rhs)?;
+        }
+        Ok(Some(locals))
+    }
 
-                fn b2f(value: bool) -> f64 {
-                    if value { 1. } else { 0. }
-                }
+    fn evaluate(
+        &self,
+        expr: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        // Each case is evaluated by its own method, so that a level of
+        // recursion only takes the stack of the cases it goes through
+        match expr.data.as_ref() {
+            IExpression::Ident(token) => self.variable(token, vars),
+            IExpression::Quantity(number, unit_expr) => self.quantity(expr, number, unit_expr),
+            IExpression::Number(number) => self.number(expr, number),
+            IExpression::Interval(lo, hi) => self.interval(expr, lo, hi, vars),
+            IExpression::Let(bindings, body) => self.let_in(bindings, body, vars),
+            IExpression::List(items) => self.list(items, vars),
+            IExpression::Str(token) => Ok(Value::Str(token.data.0.as_str().into())),
+            IExpression::Bool(token) => Ok(Value::Bool(token.data.0)),
+            IExpression::Unary(hs, op) => self.unary(expr, hs, op, vars),
+            IExpression::Binary(lhs, op, rhs)
+                if matches!(*op.data, IBinaryOperation::Custom(_)) =>
+            {
+                self.custom(expr, lhs, op, rhs, vars)
+            }
+            IExpression::Binary(lhs, op, rhs) => self.operation(expr, lhs, op, rhs, vars),
+            IExpression::Ternary(cond, lhs, rhs) => self.ternary(cond, lhs, rhs, vars),
+            IExpression::Call(token) => self.invoke(token, vars),
+        }
+    }
+
+    /// Value of the variable, function or unit named by `token`.
+    #[inline(never)]
+    fn variable(
+        &self,
+        token: &Ident<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let name = token.data.0.as_str();
+        if let Some(value) = vars.get(name) {
+            Ok(value.clone())
+        } else if let Some((name, _)) = self.lookup(name, vars) {
+            Ok(Value::Func(name.into()))
+        } else if let Some(unit) = self.units.get(name) {
+            Ok(Value::Quantity(Quantity::with_unit(1., *unit, name)))
+        } else if vars.globals.is_none() {
+            Err(EvaluateExpressionError::UndefinedLocalVar(token.clone()))
+        } else {
+            Err(EvaluateExpressionError::UndefinedVar(token.clone()))
+        }
+    }
+
+    /// Value of a number literal followed by a unit.
+    #[inline(never)]
+    fn quantity(
+        &self,
+        expr: &Expression<'a>,
+        number: &Number<'a>,
+        unit_expr: &UnitExpr<'a>,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let mut unit = Unit::new(1., Dimension::NONE);
+        for (ident, exp) in &unit_expr.data.0 {
+            let named = self
+                .units
+                .get(ident.data.0.as_str())
+                .ok_or(EvaluateExpressionError::UndefinedUnit(ident.clone()))?;
+            unit = unit * named.pow(*exp);
+        }
+        let value = match number {
+            Number::Int(token) => token.data.0 as f64,
+            Number::Float(token) => token.data.0,
+            Number::Imaginary(_) => {
+                return Err(EvaluateExpressionError::InvalidOperands(expr.clone()));
+            }
+        };
+        Ok(Value::quantity(Quantity::with_unit(
+            value,
+            unit,
+            unit_expr.pos.fragment(),
+        )))
+    }
+
+    /// Value of a number literal in the current mode.
+    #[inline(never)]
+    fn number(
+        &self,
+        expr: &Expression<'a>,
+        number: &Number<'a>,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        if self.interval {
+            return match number {
+                Number::Int(token) => Ok(Value::Interval(Interval::enclose_int(token.data.0))),
+                Number::Float(token) => Ok(Value::Interval(Interval::enclose_literal(
+                    token.pos.fragment(),
+                    token.data.0,
+                ))),
+                Number::Imaginary(_) => Err(EvaluateExpressionError::InvalidOperands(expr.clone())),
+            };
+        }
+        match number {
+            Number::Int(token) if self.decimal.is_some() => {
+                Ok(Value::Decimal(Decimal::new(token.data.0 as i128, 0)))
+            }
+            Number::Float(token) if self.decimal.is_some() => {
+                Ok(Decimal::from_f64(token.data.0)
+                    .map_or(Value::Number(token.data.0), Value::Decimal))
+            }
+            Number::Int(token) => Ok(Value::Number(token.data.0 as f64)),
+            Number::Float(token) => Ok(Value::Number(token.data.0)),
+            Number::Imaginary(token) => Ok(Value::Complex(Complex::new(0., token.data.0))),
+        }
+    }
 
-                let r = match *op.data {
-                    IBinaryOperation::Add => lr + rr,
-                    IBinaryOperation::Sub => lr - rr,
-                    IBinaryOperation::Mul => lr * rr,
-                    IBinaryOperation::Div => {
-                        if rr == 0. {
-                            return Err(EvaluateExpressionError::DivisionByZero(rhs.clone()));
-                        } else {
-                            lr / rr
+    /// Value of an interval literal `[lo .. hi]`.
+    #[inline(never)]
+    fn interval(
+        &self,
+        expr: &Expression<'a>,
+        lo: &Expression<'a>,
+        hi: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let bound = |expr: &Expression<'a>| {
+            self.evaluate(expr, vars)?
+                .as_interval()
+                .map_err(|_| EvaluateExpressionError::InvalidOperands(expr.clone()))
+        };
+        Interval::new(bound(lo)?.lo, bound(hi)?.hi)
+            .map(Value::Interval)
+            .ok_or(EvaluateExpressionError::InvalidInterval(expr.clone()))
+    }
+
+    /// Value of the body of a `let`.
+    fn let_in(
+        &self,
+        bindings: &[(Ident<'a>, Expression<'a>)],
+        body: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let scope = self.scope(bindings, vars)?;
+        self.evaluate(body, &scope)
+    }
+
+    /// Value of a builtin binary operation.
+    fn operation(
+        &self,
+        expr: &Expression<'a>,
+        lhs: &Expression<'a>,
+        op: &BinaryOperation<'a>,
+        rhs: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let lr = self.evaluate(lhs, vars)?;
+        let rr = self.evaluate(rhs, vars)?;
+        self.binary(expr, lhs, op, rhs, lr, rr)
+    }
+
+    /// Value of a user-defined operator applied to its operands.
+    fn custom(
+        &self,
+        expr: &Expression<'a>,
+        lhs: &Expression<'a>,
+        op: &BinaryOperation<'a>,
+        rhs: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let (func, token, args) = self.operator(expr, lhs, op, rhs, vars)?;
+        self.apply(func, &token, &args)
+    }
+
+    /// Value of the branch of a ternary taken for its condition.
+    fn ternary(
+        &self,
+        cond: &Expression<'a>,
+        lhs: &Expression<'a>,
+        rhs: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let expr = self.branch(cond, lhs, rhs, vars)?;
+        self.evaluate(expr, vars)
+    }
+
+    /// Branch of a ternary taken for the condition `cond`.
+    fn branch<'e>(
+        &self,
+        cond: &Expression<'a>,
+        lhs: &'e Expression<'a>,
+        rhs: &'e Expression<'a>,
+        vars: &Scope,
+    ) -> Result<&'e Expression<'a>, EvaluateExpressionError<'a>> {
+        Ok(if self.condition(cond, vars)? {
+            lhs
+        } else {
+            rhs
+        })
+    }
+
+    /// Value of the function call at `token`.
+    fn invoke(
+        &self,
+        token: &FuncCall<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let (func, args) = self.callee(token, vars)?;
+        self.apply(func, token, &args)
+    }
+
+    /// Value of a list literal.
+    #[inline(never)]
+    fn list(
+        &self,
+        items: &[Expression<'a>],
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        items
+            .iter()
+            .map(|item| self.evaluate(item, vars))
+            .collect::<Result<_, _>>()
+            .map(Value::List)
+    }
+
+    /// Looks up a user-defined operator as a function called with its two
+    /// operands.
+    #[inline(never)]
+    fn operator(
+        &self,
+        expr: &Expression<'a>,
+        lhs: &Expression<'a>,
+        op: &BinaryOperation<'a>,
+        rhs: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<(&Func<'a>, FuncCall<'a>, Vec<Value>), EvaluateExpressionError<'a>> {
+        let token = operator_call(expr, lhs, op, rhs);
+        let (func, args) = self.callee(&token, vars)?;
+        Ok((func, token, args))
+    }
+
+    /// Scope of the body of a `let`. Each binding sees the ones before it and
+    /// may shadow them.
+    #[inline(never)]
+    fn scope<'s>(
+        &self,
+        bindings: &[(Ident<'a>, Expression<'a>)],
+        vars: &Scope<'s>,
+    ) -> Result<Scope<'s>, EvaluateExpressionError<'a>> {
+        let mut scope = vars.clone();
+        for (ident, value) in bindings {
+            let value = self.evaluate(value, &scope)?;
+            scope.locals.insert(ident.data.0.clone(), value);
+        }
+        Ok(scope)
+    }
+
+    /// Value of a unary operation.
+    #[inline(never)]
+    fn unary(
+        &self,
+        expr: &Expression<'a>,
+        hs: &Expression<'a>,
+        op: &UnaryOperation<'a>,
+        vars: &Scope,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        if let IUnaryOperation::Not = *op.data {
+            return Ok(self.truth(!self.condition(hs, vars)?));
+        }
+        let hr = match self.evaluate(hs, vars)? {
+            Value::Bool(_) if self.strict => {
+                return Err(EvaluateExpressionError::TypeMismatch(
+                    hs.clone(),
+                    "number",
+                    "bool",
+                ));
+            }
+            Value::Bool(hr) => Value::Number(b2f(hr)),
+            hr => hr,
+        };
+        match *op.data {
+            IUnaryOperation::Neg => hr.neg(),
+            IUnaryOperation::Pos if hr.is_numeric() => Ok(hr),
+            IUnaryOperation::Pos => Err(ArithmeticError::InvalidOperands),
+            IUnaryOperation::Not => unreachable!(),
+        }
+        .map_err(|err| arithmetic_error(err, expr, hs))
+    }
+
+    /// Applies a builtin binary operator to the values of its operands.
+    #[inline(never)]
+    fn binary(
+        &self,
+        expr: &Expression<'a>,
+        lhs: &Expression<'a>,
+        op: &BinaryOperation<'a>,
+        rhs: &Expression<'a>,
+        lr: Value,
+        rr: Value,
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let equality = matches!(*op.data, IBinaryOperation::Eq | IBinaryOperation::Ne);
+
+        let (lr, rr) = match (lr, rr) {
+            (lr @ Value::Bool(_), rr @ Value::Bool(_)) if self.strict && equality => (lr, rr),
+            (Value::Bool(_), _) if self.strict => {
+                return Err(EvaluateExpressionError::TypeMismatch(
+                    lhs.clone(),
+                    "number",
+                    "bool",
+                ));
+            }
+            (_, Value::Bool(_)) if self.strict => {
+                return Err(EvaluateExpressionError::TypeMismatch(
+                    rhs.clone(),
+                    "number",
+                    "bool",
+                ));
+            }
+            (lr, rr) => (unbool(lr), unbool(rr)),
+        };
+        let (lr, rr) = match (lr, rr) {
+            (Value::Number(lr), rr) if self.interval => (Value::Interval(Interval::point(lr)), rr),
+            (lr, Value::Number(rr)) if self.interval => (lr, Value::Interval(Interval::point(rr))),
+            (Value::Number(lr), rr @ Value::Decimal(_)) if self.decimal.is_some() => (
+                Decimal::from_f64(lr).map_or(Value::Number(lr), Value::Decimal),
+                rr,
+            ),
+            (lr @ Value::Decimal(_), Value::Number(rr)) if self.decimal.is_some() => (
+                lr,
+                Decimal::from_f64(rr).map_or(Value::Number(rr), Value::Decimal),
+            ),
+            operands => operands,
+        };
+        if let (Some(mode), Value::Decimal(lr), Value::Decimal(rr)) = (self.decimal, &lr, &rr) {
+            match *op.data {
+                IBinaryOperation::Div => {
+                    return match lr.checked_div(*rr, mode.scale, mode.rounding) {
+                        Some(quotient) => Ok(Value::Decimal(quotient)),
+                        None if rr.is_zero() => {
+                            Err(EvaluateExpressionError::DivisionByZero(rhs.clone()))
                         }
+                        None => Err(EvaluateExpressionError::Overflow(expr.clone())),
+                    };
+                }
+                IBinaryOperation::Mul => {
+                    return lr
+                        .checked_mul(*rr, mode.rounding)
+                        .map(Value::Decimal)
+                        .ok_or_else(|| EvaluateExpressionError::Overflow(expr.clone()));
+                }
+                _ => {}
+            }
+        }
+
+        let ordering = match *op.data {
+            IBinaryOperation::Eq | IBinaryOperation::Ne => {
+                let eq = matches!(*op.data, IBinaryOperation::Eq);
+                return match lr.equals(&rr) {
+                    Ok(value) => Ok(self.truth(value == eq)),
+                    Err(ArithmeticError::InvalidOperands) if self.strict => {
+                        Err(EvaluateExpressionError::TypeMismatch(
+                            rhs.clone(),
+                            lr.type_name(),
+                            rr.type_name(),
+                        ))
                     }
-                    IBinaryOperation::Lt => b2f(lr < rr),
-                    IBinaryOperation::Le => b2f(lr <= rr),
-                    IBinaryOperation::Eq => b2f(lr == rr),
-                    IBinaryOperation::Ne => b2f(lr != rr),
-                    IBinaryOperation::Ge => b2f(lr >= rr),
-                    IBinaryOperation::Gt => b2f(lr > rr),
+                    Err(ArithmeticError::InvalidOperands) => Ok(self.truth(!eq)),
+                    Err(err) => Err(arithmetic_error(err, expr, rhs)),
                 };
-                if r.is_finite() {
-                    Ok(r)
+            }
+            IBinaryOperation::Lt
+            | IBinaryOperation::Le
+            | IBinaryOperation::Ge
+            | IBinaryOperation::Gt => lr
+                .ordering(&rr)
+                .map_err(|err| arithmetic_error(err, expr, rhs))?,
+            _ => {
+                return lr
+                    .arithmetic(&op.data, &rr)
+                    .map_err(|err| arithmetic_error(err, expr, rhs));
+            }
+        };
+        Ok(self.truth(match *op.data {
+            IBinaryOperation::Lt => ordering.is_lt(),
+            IBinaryOperation::Le => ordering.is_le(),
+            IBinaryOperation::Ge => ordering.is_ge(),
+            _ => ordering.is_gt(),
+        }))
+    }
+
+    /// Finds the function `name` and its qualified name. Imported functions
+    /// see the functions of their own module first.
+    fn lookup(&self, name: &str, vars: &Scope) -> Option<(&str, &Func<'a>)> {
+        let local = vars.namespace.and_then(|namespace| {
+            self.funcs
+                .get_key_value(format!("{namespace}.{name}").as_str())
+        });
+        local
+            .or_else(|| self.funcs.get_key_value(name))
+            .map(|(name, func)| (name.as_str(), func))
+    }
+
+    /// Looks up the function called at `token` and evaluates the arguments.
+    fn callee(
+        &self,
+        token: &FuncCall<'a>,
+        vars: &Scope,
+    ) -> Result<(&Func<'a>, Vec<Value>), EvaluateExpressionError<'a>> {
+        let (_, func) = self
+            .lookup(&token.data.ident.data.0, vars)
+            .ok_or(EvaluateExpressionError::UndefinedFunction(token.clone()))?;
+        let arity = func.arity();
+        let argc = token.data.args.data.0.len();
+        if !arity.contains(&argc) {
+            return Err(EvaluateExpressionError::InvalidFunctionArgc(
+                token.clone(),
+                if argc < *arity.start() {
+                    *arity.start()
                 } else {
-                    Err(EvaluateExpressionError::Overflow(expr.clone()))
+                    *arity.end()
+                },
+            ));
+        }
+        let args = token
+            .data
+            .args
+            .data
+            .0
+            .iter()
+            .map(|tok| self.evaluate(tok, vars))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((func, args))
+    }
+
+    /// Evaluates a function body, leaving a call of a user function in tail
+    /// position, including the branches of ternaries and the body of `let`,
+    /// to the caller.
+    fn tail<'f>(
+        &'f self,
+        expr: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<Tail<'f, 'a>, EvaluateExpressionError<'a>> {
+        match expr.data.as_ref() {
+            IExpression::Ternary(cond, lhs, rhs) => {
+                let expr = self.branch(cond, lhs, rhs, vars)?;
+                self.tail(expr, vars)
+            }
+            IExpression::Let(bindings, body) => {
+                let scope = self.scope(bindings, vars)?;
+                self.tail(body, &scope)
+            }
+            IExpression::Call(token) => self.tail_call(token, vars),
+            IExpression::Binary(lhs, op, rhs)
+                if matches!(*op.data, IBinaryOperation::Custom(_)) =>
+            {
+                let (func, token, args) = self.operator(expr, lhs, op, rhs, vars)?;
+                Ok(Tail::Call(func, token, args))
+            }
+            _ => self.evaluate(expr, vars).map(Tail::Value),
+        }
+    }
+
+    /// Leaves a call of a user function to the caller, and applies any other.
+    fn tail_call<'f>(
+        &'f self,
+        token: &FuncCall<'a>,
+        vars: &Scope,
+    ) -> Result<Tail<'f, 'a>, EvaluateExpressionError<'a>> {
+        let (func, args) = self.callee(token, vars)?;
+        match func {
+            Func::Custom(_) | Func::Piecewise(_) | Func::Imported { .. } => {
+                Ok(Tail::Call(func, token.clone(), args))
+            }
+            _ => self.apply(func, token, &args).map(Tail::Value),
+        }
+    }
+
+    /// Calls `func` with already evaluated arguments; `token` is the call site
+    /// errors are reported at.
+    pub fn apply(
+        &self,
+        func: &Func<'a>,
+        token: &FuncCall<'a>,
+        args: &[Value],
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        match func {
+            Func::Builtin { inner, .. } | Func::Variadic { inner, .. } => inner(args)
+                .map_err(|err| EvaluateExpressionError::BuiltinFunctionError(token.clone(), err)),
+            Func::Intrinsic { inner, .. } => inner(self, token, args),
+            Func::Custom(_) | Func::Piecewise(_) | Func::Imported { .. } => {
+                self.call(func, token, args)
+            }
+        }
+    }
+
+    /// Calls a user function. Tail calls replace the current call instead of
+    /// nesting in it, and all `memo` calls of the chain share its result.
+    fn call(
+        &self,
+        func: &Func<'a>,
+        token: &FuncCall<'a>,
+        args: &[Value],
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let mut call = (func, token.clone(), args.to_vec());
+        let mut keys = vec![];
+        let value = loop {
+            let (func, token, args) = &call;
+            if let Some(key) = Memo::key(self, func, args) {
+                if let Some(value) = self.memo.borrow_mut().get(&key) {
+                    break value;
                 }
+                keys.push(key);
             }
-            IExpression::Ternary(cond, lhs, rhs) => {
-                if self.evaluate_expression(cond)? != 0. {
-                    self.evaluate_expression(lhs)
-                } else {
-                    self.evaluate_expression(rhs)
+            match self.enter(func, None, token, args)? {
+                Tail::Value(value) => break value,
+                Tail::Call(func, token, args) => call = (func, token, args),
+            }
+        };
+        let mut memo = self.memo.borrow_mut();
+        for key in keys {
+            memo.results.insert(key, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Binds the arguments of a user function and evaluates its body.
+    fn enter<'f>(
+        &'f self,
+        func: &Func<'a>,
+        namespace: Option<&str>,
+        token: &FuncCall<'a>,
+        args: &[Value],
+    ) -> Result<Tail<'f, 'a>, EvaluateExpressionError<'a>> {
+        match func {
+            Func::Custom(custom_func) => {
+                let locals = custom_func
+                    .data
+                    .args
+                    .data
+                    .0
+                    .iter()
+                    .map(|ident| ident.data.0.clone())
+                    .zip(args.iter().cloned())
+                    .collect();
+                self.tail(&custom_func.data.expr, &Scope::function(locals, namespace))
+            }
+            Func::Piecewise(func) => self.clause(func, namespace, token, args),
+            Func::Imported { namespace, func } => self.enter(func, Some(namespace), token, args),
+            _ => self.apply(func, token, args).map(Tail::Value),
+        }
+    }
+
+    /// Evaluates the first clause of `func` whose pattern and guard match.
+    fn clause<'f>(
+        &'f self,
+        func: &Piecewise<'a>,
+        namespace: Option<&str>,
+        token: &FuncCall<'a>,
+        args: &[Value],
+    ) -> Result<Tail<'f, 'a>, EvaluateExpressionError<'a>> {
+        for clause in &func.data.clauses {
+            let Some(locals) = self.bind(&clause.data, args)? else {
+                continue;
+            };
+            let scope = Scope::function(locals, namespace);
+            for (guard, expr) in &clause.data.guards {
+                let taken = match guard {
+                    Some(guard) => self.condition(guard, &scope)?,
+                    None => true,
+                };
+                if taken {
+                    return self.tail(expr, &scope);
                 }
             }
-            IExpression::Call(token) => {
-                let argc = token.data.args.data.0.len();
-                match self
-                    .funcs
-                    .get(token.data.ident.data.0.as_str())
-                    .ok_or(EvaluateExpressionError::UndefinedFunction(token.clone()))?
-                {
-                    Func::Builtin {
-                        argc: builtin_func_argc,
-                        inner: builtin_func_inner,
-                    } => {
-                        if *builtin_func_argc != argc {
-                            return Err(EvaluateExpressionError::InvalidFunctionArgc(
-                                token.clone(),
-                                *builtin_func_argc,
-                            ));
-                        }
-                        let mut args = vec![0.; argc];
-                        for (idx, tok) in token.data.args.data.0.iter().enumerate() {
-                            args[idx] = self.evaluate_expression(tok)?;
-                        }
-                        builtin_func_inner(&args).map_err(|err| {
-                            EvaluateExpressionError::BuiltinFunctionError(
-                                token.clone(),
-                                err,
-                            )
-                        })
-                    }
-                    Func::Custom(custom_func) => {
-                        let custom_func_argc = custom_func.data.args.data.0.len();
-                        if custom_func_argc != argc {
-                            return Err(EvaluateExpressionError::InvalidFunctionArgc(
-                                token.clone(),
-                                custom_func_argc,
-                            ));
-                        }
-                        let mut ctx: Context<'a> = Context {
-                            funcs: self.funcs.clone(),
-                            vars: HashMap::new(),
-                        };
-                        for (idx, i) in token.data.args.data.0.iter().enumerate() {
-                            ctx.vars.insert(
-                                custom_func.data.args.data.0[idx].data.0.clone(),
-                                self.evaluate_expression(i)?,
-                            );
-                        }
-                        ctx.evaluate_expression(&custom_func.data.expr)
-                    }
+        }
+        Err(EvaluateExpressionError::NoMatchingClause(
+            token.clone(),
+            args.to_vec(),
+        ))
+    }
+
+    /// Calls the function referenced by `func` on behalf of the builtin called at `token`.
+    pub fn call_value(
+        &self,
+        func: &Value,
+        token: &FuncCall<'a>,
+        args: &[Value],
+    ) -> Result<Value, EvaluateExpressionError<'a>> {
+        let error = |err| EvaluateExpressionError::BuiltinFunctionError(token.clone(), err);
+        let Value::Func(name) = func else {
+            return Err(error(format!(
+                "Expected function, got {}",
+                func.type_name()
+            )));
+        };
+        let func = self
+            .funcs
+            .get(name.as_ref())
+            .ok_or_else(|| error(format!("Undefined function '{name}'")))?;
+        if !func.arity().contains(&args.len()) {
+            return Err(error(format!(
+                "Function '{name}' cannot be called with {} arguments",
+                args.len()
+            )));
+        }
+        self.apply(func, token, args)
+    }
+
+    /// Evaluates the unary function `func` at `x` for the numerical methods.
+    fn sample(
+        &self,
+        func: &Value,
+        token: &FuncCall<'a>,
+        x: f64,
+    ) -> Result<f64, EvaluateExpressionError<'a>> {
+        let error = |err| EvaluateExpressionError::BuiltinFunctionError(token.clone(), err);
+        let y = self
+            .call_value(func, token, &[Value::Number(x)])?
+            .as_number()
+            .map_err(error)?;
+        if y.is_finite() {
+            Ok(y)
+        } else {
+            Err(error(format!("Function is not finite at {x}")))
+        }
+    }
+
+    /// Evaluates the unary function `func` on a dual number.
+    fn sample_dual(
+        &self,
+        func: &Value,
+        token: &FuncCall<'a>,
+        x: Dual,
+    ) -> Result<Dual, EvaluateExpressionError<'a>> {
+        self.call_value(func, token, &[Value::Dual(x)])?
+            .as_dual()
+            .map_err(|err| EvaluateExpressionError::BuiltinFunctionError(token.clone(), err))
+    }
+
+    /// Differentiates `func` with respect to its `index`-th argument at `point`
+    /// by evaluating it once on dual numbers.
+    fn partial(
+        &self,
+        func: &Value,
+        token: &FuncCall<'a>,
+        point: &[f64],
+        index: usize,
+    ) -> Result<f64, EvaluateExpressionError<'a>> {
+        let args: Vec<Value> = point
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                if i == index {
+                    Value::Dual(Dual::variable(x))
+                } else {
+                    Value::Number(x)
+                }
+            })
+            .collect();
+        match self.call_value(func, token, &args)? {
+            Value::Dual(dual) if dual.is_finite() => Ok(dual.du),
+            Value::Dual(_) => Err(EvaluateExpressionError::BuiltinFunctionError(
+                token.clone(),
+                "Derivative is not finite".to_string(),
+            )),
+            Value::Number(_) | Value::Bool(_) => Ok(0.),
+            other => Err(EvaluateExpressionError::BuiltinFunctionError(
+                token.clone(),
+                format!(
+                    "Cannot differentiate a function returning {}",
+                    other.type_name()
+                ),
+            )),
+        }
+    }
+
+    /// Evaluates `expr` as a condition. In strict mode only booleans are accepted.
+    fn condition(
+        &self,
+        expr: &Expression<'a>,
+        vars: &Scope,
+    ) -> Result<bool, EvaluateExpressionError<'a>> {
+        match self.evaluate(expr, vars)? {
+            Value::Bool(value) => Ok(value),
+            value if self.strict => Err(EvaluateExpressionError::TypeMismatch(
+                expr.clone(),
+                "bool",
+                value.type_name(),
+            )),
+            value => Ok(value.is_truthy()),
+        }
+    }
+
+    /// Represents the outcome of a comparison or logical operation. Decimal
+    /// mode keeps it a boolean too, rather than a number rounded to its scale.
+    fn truth(&self, value: bool) -> Value {
+        if self.strict || self.decimal.is_some() {
+            Value::Bool(value)
+        } else {
+            Value::Number(b2f(value))
+        }
+    }
+}
+
+fn b2f(value: bool) -> f64 {
+    if value { 1. } else { 0. }
+}
+
+fn unbool(value: Value) -> Value {
+    match value {
+        Value::Bool(value) => Value::Number(b2f(value)),
+        value => value,
+    }
+}
+
+/// Collects numbers from variadic arguments, expanding lists.
+fn samples(args: &[Value]) -> Result<Vec<f64>, String> {
+    let mut values = Vec::new();
+    for arg in args {
+        match arg {
+            Value::List(items) => {
+                for item in items.iter() {
+                    values.push(item.as_number()?);
                 }
             }
+            arg => values.push(arg.as_number()?),
+        }
+    }
+    Ok(values)
+}
+
+fn nonempty_samples(args: &[Value]) -> Result<Vec<f64>, String> {
+    let values = samples(args)?;
+    if values.is_empty() {
+        return Err("Expected at least one value".to_string());
+    }
+    Ok(values)
+}
+
+/// Collects intervals from variadic arguments like [`samples`], if any of
+/// them is an interval.
+fn interval_samples(args: &[Value]) -> Result<Option<Vec<Interval>>, String> {
+    let mut values = Vec::new();
+    let mut intervals = false;
+    for arg in args {
+        let items = match arg {
+            Value::List(items) => items.as_ref(),
+            arg => slice::from_ref(arg),
+        };
+        for item in items {
+            intervals |= matches!(item, Value::Interval(_));
+            values.push(item.as_interval()?);
+        }
+    }
+    Ok(intervals.then_some(values))
+}
+
+fn real_arg<'a>(call: &FuncCall<'a>, arg: &Value) -> Result<f64, EvaluateExpressionError<'a>> {
+    arg.as_number()
+        .map_err(|err| EvaluateExpressionError::BuiltinFunctionError(call.clone(), err))
+}
+
+/// Reads the optional tolerance and step limit of a numerical method.
+fn solver_options<'a>(
+    call: &FuncCall<'a>,
+    args: &[Value],
+    tol: f64,
+    max_steps: usize,
+) -> Result<(f64, usize), EvaluateExpressionError<'a>> {
+    let error = |err: &str| EvaluateExpressionError::BuiltinFunctionError(call.clone(), err.into());
+    let tol = match args.first() {
+        Some(arg) => real_arg(call, arg)?,
+        None => tol,
+    };
+    if tol.is_nan() || tol <= 0. {
+        return Err(error("Tolerance must be positive"));
+    }
+    let max_steps = match args.get(1) {
+        Some(arg) => match real_arg(call, arg)? {
+            steps if steps >= 1. && steps.fract() == 0. => steps as usize,
+            _ => return Err(error("Step limit must be a positive integer")),
+        },
+        None => max_steps,
+    };
+    Ok((tol, max_steps))
+}
+
+fn numeric_error<'a>(
+    call: &FuncCall<'a>,
+    err: NumericError<EvaluateExpressionError<'a>>,
+) -> EvaluateExpressionError<'a> {
+    match err {
+        NumericError::Function(err) => err,
+        NumericError::NoBracket => EvaluateExpressionError::NoBracket(call.clone()),
+        NumericError::NoConvergence(steps) => {
+            EvaluateExpressionError::NoConvergence(call.clone(), steps)
+        }
+    }
+}
+
+/// Attributes an arithmetic failure to the expression that caused it:
+/// division by zero points at the divisor, everything else at the whole operation.
+fn arithmetic_error<'a>(
+    err: ArithmeticError,
+    expr: &Expression<'a>,
+    divisor: &Expression<'a>,
+) -> EvaluateExpressionError<'a> {
+    match err {
+        ArithmeticError::InvalidOperands => EvaluateExpressionError::InvalidOperands(expr.clone()),
+        ArithmeticError::DivisionByZero => EvaluateExpressionError::DivisionByZero(divisor.clone()),
+        ArithmeticError::Overflow => EvaluateExpressionError::Overflow(expr.clone()),
+        ArithmeticError::DimensionMismatch(lhs, rhs) => {
+            EvaluateExpressionError::DimensionMismatch(expr.clone(), lhs, rhs)
+        }
+        ArithmeticError::Indeterminate => {
+            EvaluateExpressionError::IndeterminateComparison(expr.clone())
         }
     }
 }
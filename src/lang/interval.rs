@@ -0,0 +1,356 @@
+use std::f64::consts::{PI, TAU};
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Closed interval `[lo, hi]`. Every operation rounds its bounds outwards,
+/// so the result is guaranteed to enclose the exact result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Option<Self> {
+        (lo <= hi).then_some(Self { lo, hi })
+    }
+
+    pub fn point(value: f64) -> Self {
+        Self {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    /// Smallest interval enclosing the integer `int`.
+    pub fn enclose_int(int: i64) -> Self {
+        let value = int as f64;
+        if value as i128 == int as i128 {
+            Self::point(value)
+        } else {
+            Self::around(value)
+        }
+    }
+
+    /// Smallest interval enclosing the decimal literal `text`, which was parsed as `value`.
+    pub fn enclose_literal(text: &str, value: f64) -> Self {
+        let exact = significand(&format!("{value:.1100}"));
+        if exact.is_some() && significand(text) == exact {
+            Self::point(value)
+        } else {
+            Self::around(value)
+        }
+    }
+
+    /// The neighbours of `value`, for exact values that `value` only rounds.
+    fn around(value: f64) -> Self {
+        Self {
+            lo: value.next_down(),
+            hi: value.next_up(),
+        }
+    }
+
+    pub fn contains_zero(self) -> bool {
+        self.lo <= 0. && 0. <= self.hi
+    }
+
+    pub fn is_point(self) -> bool {
+        self.lo == self.hi
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.lo.is_finite() && self.hi.is_finite()
+    }
+
+    pub fn width(self) -> f64 {
+        sub_up(self.hi, self.lo)
+    }
+
+    /// `None` if the divisor contains zero.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.contains_zero() {
+            return None;
+        }
+        let pairs = [
+            (self.lo, rhs.lo),
+            (self.lo, rhs.hi),
+            (self.hi, rhs.lo),
+            (self.hi, rhs.hi),
+        ];
+        Some(Self {
+            lo: pairs
+                .iter()
+                .map(|(a, b)| div_down(*a, *b))
+                .fold(f64::INFINITY, f64::min),
+            hi: pairs
+                .iter()
+                .map(|(a, b)| div_up(*a, *b))
+                .fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+
+    pub fn abs(self) -> Self {
+        if self.lo >= 0. {
+            self
+        } else if self.hi <= 0. {
+            -self
+        } else {
+            Self {
+                lo: 0.,
+                hi: self.hi.max(-self.lo),
+            }
+        }
+    }
+
+    /// `None` if the interval has negative values.
+    pub fn sqrt(self) -> Option<Self> {
+        (self.lo >= 0.).then(|| Self {
+            lo: sqrt_down(self.lo),
+            hi: sqrt_up(self.hi),
+        })
+    }
+
+    // `exp` and `ln` are not correctly rounded, so their bounds are widened by one ulp
+
+    pub fn exp(self) -> Self {
+        Self {
+            lo: self.lo.exp().next_down().max(0.),
+            hi: self.hi.exp().next_up(),
+        }
+    }
+
+    /// `None` if the interval has non-positive values.
+    pub fn ln(self) -> Option<Self> {
+        (self.lo > 0.).then(|| Self {
+            lo: self.lo.ln().next_down(),
+            hi: self.hi.ln().next_up(),
+        })
+    }
+
+    /// Writes the interval with its bounds written by `number`.
+    pub fn format_with(&self, number: impl Fn(f64) -> String) -> String {
+        format!("[{} .. {}]", number(self.lo), number(self.hi))
+    }
+
+    /// Encloses the smaller of a value in `self` and one in `rhs`.
+    pub fn min(self, rhs: Self) -> Self {
+        Self {
+            lo: self.lo.min(rhs.lo),
+            hi: self.hi.min(rhs.hi),
+        }
+    }
+
+    /// Encloses the larger of a value in `self` and one in `rhs`.
+    pub fn max(self, rhs: Self) -> Self {
+        Self {
+            lo: self.lo.max(rhs.lo),
+            hi: self.hi.max(rhs.hi),
+        }
+    }
+
+    pub fn sin(self) -> Self {
+        self.wave(f64::sin, 0.5)
+    }
+
+    pub fn cos(self) -> Self {
+        self.wave(f64::cos, 0.)
+    }
+
+    /// Bounds of `f`, a sine wave peaking at `(2k + phase)π` and bottoming out
+    /// halfway between. Extrema count as inside whenever they may be.
+    fn wave(self, f: fn(f64) -> f64, phase: f64) -> Self {
+        if !self.is_finite() || self.width() >= TAU {
+            return Self { lo: -1., hi: 1. };
+        }
+        let (start, end) = (self.lo / PI - phase, self.hi / PI - phase);
+        let slack = |x: f64| (x.abs() + 1.) * 4. * f64::EPSILON;
+        let first = (start - slack(start)).ceil();
+        let last = (end + slack(end)).floor();
+        let (a, b) = (f(self.lo), f(self.hi));
+        let mut bounds = Self {
+            lo: a.min(b).next_down().max(-1.),
+            hi: a.max(b).next_up().min(1.),
+        };
+        // Even multiples are peaks and odd ones troughs
+        if first <= last {
+            if last > first || first.rem_euclid(2.) == 0. {
+                bounds.hi = 1.;
+            }
+            if last > first || first.rem_euclid(2.) == 1. {
+                bounds.lo = -1.;
+            }
+        }
+        bounds
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            lo: add_down(self.lo, rhs.lo),
+            hi: add_up(self.hi, rhs.hi),
+        }
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl Mul for Interval {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let pairs = [
+            (self.lo, rhs.lo),
+            (self.lo, rhs.hi),
+            (self.hi, rhs.lo),
+            (self.hi, rhs.hi),
+        ];
+        Self {
+            lo: pairs
+                .iter()
+                .map(|(a, b)| mul_down(*a, *b))
+                .fold(f64::INFINITY, f64::min),
+            hi: pairs
+                .iter()
+                .map(|(a, b)| mul_up(*a, *b))
+                .fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_with(|bound| bound.to_string()))
+    }
+}
+
+/// Significant digits of the decimal `text`, like `-1_000.50e-2`, with its
+/// sign, and the exponent that makes them `±0.digits × 10^exponent`.
+fn significand(text: &str) -> Option<(String, i64)> {
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(text) => ("-", text),
+        None => ("", text),
+    };
+    let text = text.replace('_', "");
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (text.as_str(), 0),
+    };
+    let (integral, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{integral}{fraction}");
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    let digits = digits.trim_matches('0');
+    if digits.is_empty() {
+        return Some((String::new(), 0));
+    }
+    let exponent = exponent.checked_add(integral.len() as i64 - leading as i64)?;
+    Some((format!("{sign}{digits}"), exponent))
+}
+
+// Directed rounding: the rounding error of `+`, `*`, `/` and `sqrt` is
+// recovered exactly (TwoSum and FMA residuals) to decide which way to step.
+
+fn add_err(a: f64, b: f64, sum: f64) -> f64 {
+    let bb = sum - a;
+    (a - (sum - bb)) + (b - bb)
+}
+
+fn add_down(a: f64, b: f64) -> f64 {
+    let sum = a + b;
+    if add_err(a, b, sum) < 0. {
+        sum.next_down()
+    } else {
+        sum
+    }
+}
+
+fn add_up(a: f64, b: f64) -> f64 {
+    let sum = a + b;
+    if add_err(a, b, sum) > 0. {
+        sum.next_up()
+    } else {
+        sum
+    }
+}
+
+fn sub_up(a: f64, b: f64) -> f64 {
+    add_up(a, -b)
+}
+
+fn mul_down(a: f64, b: f64) -> f64 {
+    let product = a * b;
+    if a.mul_add(b, -product) < 0. {
+        product.next_down()
+    } else {
+        product
+    }
+}
+
+fn mul_up(a: f64, b: f64) -> f64 {
+    let product = a * b;
+    if a.mul_add(b, -product) > 0. {
+        product.next_up()
+    } else {
+        product
+    }
+}
+
+/// Sign of the difference between `a / b` and the rounded quotient.
+fn div_err(a: f64, b: f64, quotient: f64) -> f64 {
+    (-quotient).mul_add(b, a) * b.signum()
+}
+
+fn div_down(a: f64, b: f64) -> f64 {
+    let quotient = a / b;
+    if div_err(a, b, quotient) < 0. {
+        quotient.next_down()
+    } else {
+        quotient
+    }
+}
+
+fn div_up(a: f64, b: f64) -> f64 {
+    let quotient = a / b;
+    if div_err(a, b, quotient) > 0. {
+        quotient.next_up()
+    } else {
+        quotient
+    }
+}
+
+fn sqrt_down(a: f64) -> f64 {
+    let root = a.sqrt();
+    if (-root).mul_add(root, a) < 0. {
+        root.next_down()
+    } else {
+        root
+    }
+}
+
+fn sqrt_up(a: f64) -> f64 {
+    let root = a.sqrt();
+    if (-root).mul_add(root, a) > 0. {
+        root.next_up()
+    } else {
+        root
+    }
+}
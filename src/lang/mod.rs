@@ -1,9 +1,31 @@
+mod complex;
+mod decimal;
+mod display;
+mod dual;
 mod interpret;
+mod interval;
+mod numeric;
 mod parser;
+mod random;
+mod stats;
+mod symbolic;
 mod tokens;
 mod types;
+mod units;
+mod value;
 
+pub use complex::*;
+pub use decimal::*;
+pub use display::*;
+pub use dual::*;
 pub use interpret::*;
+pub use interval::*;
+pub use numeric::*;
 pub use parser::*;
+pub use random::*;
+pub use stats::*;
+pub use symbolic::*;
 pub use tokens::*;
 pub use types::*;
+pub use units::*;
+pub use value::*;
@@ -0,0 +1,156 @@
+use super::dual::Dual;
+
+/// Failure of a numerical method, or of the function it evaluates.
+#[derive(Debug)]
+pub enum NumericError<E> {
+    Function(E),
+    NoBracket,
+    NoConvergence(usize),
+}
+
+/// Finds a root of `f` between `lo` and `hi` by bisection. The function must
+/// change sign over the interval.
+pub fn bisect<E>(
+    mut f: impl FnMut(f64) -> Result<f64, E>,
+    mut lo: f64,
+    mut hi: f64,
+    tol: f64,
+    max_iter: usize,
+) -> Result<f64, NumericError<E>> {
+    let mut f_lo = f(lo).map_err(NumericError::Function)?;
+    let f_hi = f(hi).map_err(NumericError::Function)?;
+    if f_lo == 0. {
+        return Ok(lo);
+    }
+    if f_hi == 0. {
+        return Ok(hi);
+    }
+    if f_lo.signum() == f_hi.signum() {
+        return Err(NumericError::NoBracket);
+    }
+    for _ in 0..max_iter {
+        let mid = lo + (hi - lo) / 2.;
+        if (hi - lo).abs() / 2. <= tol {
+            return Ok(mid);
+        }
+        let f_mid = f(mid).map_err(NumericError::Function)?;
+        if f_mid == 0. {
+            return Ok(mid);
+        }
+        if f_mid.signum() == f_lo.signum() {
+            lo = mid;
+            f_lo = f_mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Err(NumericError::NoConvergence(max_iter))
+}
+
+/// Finds a root of `f` starting from `x0` with Newton's method. `f` is
+/// evaluated on dual numbers to obtain its derivative.
+pub fn newton<E>(
+    mut f: impl FnMut(Dual) -> Result<Dual, E>,
+    mut x: f64,
+    tol: f64,
+    max_iter: usize,
+) -> Result<f64, NumericError<E>> {
+    for _ in 0..max_iter {
+        let y = f(Dual::variable(x)).map_err(NumericError::Function)?;
+        if y.re == 0. {
+            return Ok(x);
+        }
+        let step = y.re / y.du;
+        if !step.is_finite() {
+            break;
+        }
+        x -= step;
+        if step.abs() <= tol {
+            return Ok(x);
+        }
+    }
+    Err(NumericError::NoConvergence(max_iter))
+}
+
+/// Integrates `f` from `a` to `b` with adaptive Simpson's rule, halving each
+/// subinterval at most `max_depth` times.
+pub fn simpson<E>(
+    mut f: impl FnMut(f64) -> Result<f64, E>,
+    a: f64,
+    b: f64,
+    tol: f64,
+    max_depth: usize,
+) -> Result<f64, NumericError<E>> {
+    let m = a + (b - a) / 2.;
+    let (fa, fm, fb) = (
+        f(a).map_err(NumericError::Function)?,
+        f(m).map_err(NumericError::Function)?,
+        f(b).map_err(NumericError::Function)?,
+    );
+    let whole = (b - a) / 6. * (fa + 4. * fm + fb);
+    simpson_step(&mut f, [a, m, b], [fa, fm, fb], whole, tol, max_depth).map_err(|err| match err {
+        NumericError::NoConvergence(_) => NumericError::NoConvergence(max_depth),
+        err => err,
+    })
+}
+
+fn simpson_step<E>(
+    f: &mut impl FnMut(f64) -> Result<f64, E>,
+    [a, m, b]: [f64; 3],
+    [fa, fm, fb]: [f64; 3],
+    whole: f64,
+    tol: f64,
+    depth: usize,
+) -> Result<f64, NumericError<E>> {
+    let (lm, rm) = (a + (m - a) / 2., m + (b - m) / 2.);
+    let (flm, frm) = (
+        f(lm).map_err(NumericError::Function)?,
+        f(rm).map_err(NumericError::Function)?,
+    );
+    let left = (m - a) / 6. * (fa + 4. * flm + fm);
+    let right = (b - m) / 6. * (fm + 4. * frm + fb);
+    let delta = left + right - whole;
+    if delta.abs() <= 15. * tol {
+        // Richardson extrapolation
+        return Ok(left + right + delta / 15.);
+    }
+    if depth == 0 {
+        return Err(NumericError::NoConvergence(0));
+    }
+    Ok(
+        simpson_step(f, [a, lm, m], [fa, flm, fm], left, tol / 2., depth - 1)?
+            + simpson_step(f, [m, rm, b], [fm, frm, fb], right, tol / 2., depth - 1)?,
+    )
+}
+
+/// Finds a minimum of a unimodal `f` between `lo` and `hi` by golden-section
+/// search.
+pub fn golden_section<E>(
+    mut f: impl FnMut(f64) -> Result<f64, E>,
+    lo: f64,
+    hi: f64,
+    tol: f64,
+    max_iter: usize,
+) -> Result<f64, NumericError<E>> {
+    let ratio = (5f64.sqrt() - 1.) / 2.;
+    let (mut a, mut b) = (lo.min(hi), lo.max(hi));
+    let mut c = b - ratio * (b - a);
+    let mut d = a + ratio * (b - a);
+    let mut fc = f(c).map_err(NumericError::Function)?;
+    let mut fd = f(d).map_err(NumericError::Function)?;
+    for _ in 0..max_iter {
+        if b - a <= tol {
+            return Ok(a + (b - a) / 2.);
+        }
+        if fc < fd {
+            (b, d, fd) = (d, c, fc);
+            c = b - ratio * (b - a);
+            fc = f(c).map_err(NumericError::Function)?;
+        } else {
+            (a, c, fc) = (c, d, fd);
+            d = a + ratio * (b - a);
+            fd = f(d).map_err(NumericError::Function)?;
+        }
+    }
+    Err(NumericError::NoConvergence(max_iter))
+}
@@ -1,24 +1,58 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::iter;
+use std::mem;
 
 use super::tokens::*;
 use super::types::*;
 use nom::bytes::complete::tag;
-use nom::character::complete::space0;
+use nom::character::complete::{space0, space1};
 use nom::combinator::cut;
+use nom::combinator::eof;
+use nom::combinator::not;
 use nom::combinator::opt;
+use nom::combinator::peek;
 use nom::sequence::delimited;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
 use nom::{
     Input, Offset, Parser,
     branch::alt,
     character::complete::{char, digit1, satisfy},
     combinator::value,
-    multi::many0,
+    multi::{many0, many1, separated_list0, separated_list1},
 };
 
-fn ws<'a, F: Parser<Span<'a>>>(
+/// Spaces and block comments, which may appear wherever spaces can. A block
+/// comment ending the line is left to be parsed as [`Program::Comment`].
+fn blank(input: Span) -> Result<()> {
+    many0(alt((
+        space1.map(|_| ()),
+        terminated(no_ws_block_comment, not(line_rest)).map(|_| ()),
+    )))
+    .map(|_| ())
+    .parse(input)
+}
+
+/// The rest of a line after a statement: spaces and block comments up to a
+/// newline, a line comment or the end of input.
+fn line_rest(input: Span) -> Result<()> {
+    (
+        many0(alt((space1.map(|_| ()), no_ws_block_comment.map(|_| ())))),
+        alt((
+            eol.map(|_| ()),
+            peek(char('#')).map(|_| ()),
+            eof.map(|_| ()),
+        )),
+    )
+        .map(|_| ())
+        .parse(input)
+}
+
+fn ws<'a, F: Parser<Span<'a>, Error = Error<'a>>>(
     inner: F,
 ) -> impl Parser<Span<'a>, Output = F::Output, Error = F::Error> {
-    delimited(space0, inner, space0)
+    delimited(blank, inner, blank)
 }
 
 fn parsed<'a, F: Parser<Span<'a>>>(
@@ -33,9 +67,12 @@ fn parsed<'a, F: Parser<Span<'a>>>(
 macro_rules! verbose_char {
     ($ident:ident, $ch:literal, $inner:path, $outer:path) => {
         pub fn $ident(input: Span) -> Result<$outer> {
-            ws(parsed(char::<_, ()>($ch)))
-                .map(|(_, diff)| Token::new(diff, $inner))
-                .parse_or(input, concat!("Expected '", $ch, "'"))
+            ws(|input| {
+                parsed(char::<_, ()>($ch))
+                    .map(|(_, diff)| Token::new(diff, $inner))
+                    .parse_or(input, concat!("Expected '", $ch, "'"))
+            })
+            .parse(input)
         }
     };
 }
@@ -45,6 +82,10 @@ verbose_char!(lpar, '(', ILpar, Lpar);
 verbose_char!(rpar, ')', IRpar, Rpar);
 verbose_char!(que, '?', IQue, Que);
 verbose_char!(col, ':', ICol, Col);
+verbose_char!(lbrk, '[', ILbrk, Lbrk);
+verbose_char!(rbrk, ']', IRbrk, Rbrk);
+verbose_char!(lbrc, '{', ILbrc, Lbrc);
+verbose_char!(rbrc, '}', IRbrc, Rbrc);
 
 pub fn eol(input: Span) -> Result<Eol> {
     alt((tag::<_, _, ()>("\r\n"), tag("\n")))
@@ -52,6 +93,78 @@ pub fn eol(input: Span) -> Result<Eol> {
         .parse_or(input, "Expected EOL")
 }
 
+/// End of a statement: a newline, or a comment running up to it. The comment
+/// itself is left to be parsed as [`Program::Comment`].
+fn end(input: Span) -> Result<Span> {
+    alt((
+        eol.map(|eol| eol.pos),
+        peek(no_ws_line_comment).map(|comment| comment.pos),
+        peek(terminated(no_ws_block_comment, line_rest)).map(|comment| comment.pos),
+    ))
+    .parse_or(input, "Expected EOL")
+}
+
+/// End of the last statement of a script, which may lack the newline.
+fn end_or_eof(input: Span) -> Result<Span> {
+    alt((end, eof)).parse_or(input, "Expected EOL")
+}
+
+/// End of a statement inside a block, which may also be the closing brace.
+fn block_end(input: Span) -> Result<Span> {
+    alt((end, peek(rbrc).map(|rb| rb.pos))).parse_or(input, "Expected EOL")
+}
+
+/// Parser for the end of a statement.
+type Terminator = for<'a> fn(Span<'a>) -> Result<'a, Span<'a>>;
+
+fn no_ws_line_comment(input: Span) -> Result<Comment> {
+    let (rest, _) = char::<_, ()>('#').parse_or(input, "Expected comment")?;
+    let len = rest.find(['\r', '\n']).unwrap_or(rest.len());
+    let (rest, text) = rest.take_split(len);
+    Ok((
+        rest,
+        Token::new(input.diff(&rest), IComment(text.to_string())),
+    ))
+}
+
+fn no_ws_block_comment(input: Span) -> Result<Comment> {
+    let (rest, _) = tag::<_, _, ()>("/*").parse_or(input, "Expected comment")?;
+    let Some(len) = rest.find("*/") else {
+        return Err(nom::Err::Failure(Error::new(
+            input,
+            "Unterminated block comment",
+        )));
+    };
+    let (rest, text) = rest.take_split(len);
+    let rest = rest.take_from(2);
+    Ok((
+        rest,
+        Token::new(input.diff(&rest), IComment(text.to_string())),
+    ))
+}
+
+/// Whitespace that may span lines: spaces, comments and newlines. Allowed
+/// where an expression cannot end, such as after an operator.
+fn gap(input: Span) -> Result<()> {
+    many0(alt((
+        space1.map(|_| ()),
+        no_ws_block_comment.map(|_| ()),
+        no_ws_line_comment.map(|_| ()),
+        eol.map(|_| ()),
+    )))
+    .map(|_| ())
+    .parse(input)
+}
+
+pub fn comment(input: Span) -> Result<Comment> {
+    delimited(
+        space0,
+        alt((no_ws_line_comment, no_ws_block_comment)),
+        space0,
+    )
+    .parse(input)
+}
+
 fn no_ws_integer(input: Span) -> Result<Int> {
     let (rest, int) = digit1::<_, ()>.parse_or(input, "Cannot instantiate integer")?;
     match int.parse::<i64>() {
@@ -64,46 +177,270 @@ fn no_ws_integer(input: Span) -> Result<Int> {
 }
 
 fn no_ws_number(input: Span) -> Result<Number> {
-    let (rest, integral) = no_ws_integer(input)?;
-    // Nah I'd simplify
-    if !rest.starts_with('.') {
-        return Ok((rest, Number::Int(integral)));
+    let (rest, number) = no_ws_real(input)?;
+    // An `i` suffix not followed by an identifier char makes the literal imaginary
+    let mut chars = rest.chars();
+    if chars.next() != Some('i')
+        || chars
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+    {
+        return Ok((rest, number));
     }
-    let (rest, rational) = no_ws_integer.parse_or(
-        rest.take_from(1),
-        "Cannot instantiate rational part of float",
-    )?;
-    if let Ok(float) = format!("{}.{}", integral.data.0, rational.data.0).parse() {
-        Ok((
+    let rest = rest.take_from(1);
+    let value = match number {
+        Number::Int(token) => token.data.0 as f64,
+        Number::Float(token) => token.data.0,
+        Number::Imaginary(_) => unreachable!(),
+    };
+    Ok((
+        rest,
+        Number::Imaginary(Token::new(input.diff(&rest), IImaginary(value))),
+    ))
+}
+
+/// Consumes digits of `radix`, optionally separated by single underscores, and
+/// returns them without the separators.
+fn no_ws_digits(input: Span, radix: u32) -> Result<String> {
+    let mut digits = String::new();
+    let mut rest = input;
+    loop {
+        match rest.chars().next() {
+            Some(c) if c.is_digit(radix) => digits.push(c),
+            Some('_') if !digits.is_empty() => {
+                if !rest.chars().nth(1).is_some_and(|c| c.is_digit(radix)) {
+                    return Err(nom::Err::Failure(Error::new(
+                        rest,
+                        "Digit separator should be followed by a digit",
+                    )));
+                }
+            }
+            _ => break,
+        }
+        rest = rest.take_from(1);
+    }
+    if digits.is_empty() {
+        return Err(nom::Err::Error(Error::new(input, "Expected digits")));
+    }
+    Ok((rest, digits))
+}
+
+/// Makes a missing part of a literal that has already started a hard error.
+fn required<'a, T>(result: Result<'a, T>, input: Span<'a>, message: &'static str) -> Result<'a, T> {
+    result.map_err(|err| match err {
+        nom::Err::Error(_) => nom::Err::Failure(Error::new(input, message)),
+        err => err,
+    })
+}
+
+fn no_ws_real(input: Span) -> Result<Number> {
+    let prefix = input.get(..2).map(str::to_ascii_lowercase);
+    let radix = match prefix.as_deref() {
+        Some("0x") => Some((16, "hexadecimal")),
+        Some("0o") => Some((8, "octal")),
+        Some("0b") => Some((2, "binary")),
+        _ => None,
+    };
+    if let Some((radix, name)) = radix {
+        return no_ws_radix_integer(input, radix, name);
+    }
+    let (rest, integral) = match no_ws_digits(input, 10) {
+        Ok((rest, digits)) => (rest, Some(digits)),
+        Err(nom::Err::Error(_)) => (input, None),
+        Err(err) => return Err(err),
+    };
+    // `1..2` is a range, not a float
+    let (rest, fraction) = if rest.starts_with('.') && !rest.starts_with("..") {
+        let digits = no_ws_digits(rest.take_from(1), 10);
+        let (rest, digits) = match integral {
+            Some(_) => required(
+                digits,
+                rest.take_from(1),
+                "Expected digits after decimal point",
+            )?,
+            None => digits.map_err(|_| nom::Err::Error(Error::new(input, "Expected number")))?,
+        };
+        (rest, Some(digits))
+    } else {
+        (rest, None)
+    };
+    if integral.is_none() && fraction.is_none() {
+        return Err(nom::Err::Error(Error::new(input, "Expected number")));
+    }
+    let (rest, exponent) = no_ws_exponent(rest)?;
+    if fraction.is_none() && exponent.is_none() {
+        return match integral.unwrap_or_default().parse::<i64>() {
+            Ok(int) => Ok((rest, Number::Int(Token::new(input.diff(&rest), IInt(int))))),
+            Err(_) => Err(nom::Err::Failure(Error::new(
+                input,
+                "Integer literal is too large",
+            ))),
+        };
+    }
+    let text = format!(
+        "{}.{}e{}",
+        integral.as_deref().unwrap_or("0"),
+        fraction.as_deref().unwrap_or("0"),
+        exponent.as_deref().unwrap_or("0"),
+    );
+    match text.parse::<f64>() {
+        Ok(float) if float.is_finite() => Ok((
             rest,
             Number::Float(Token::new(input.diff(&rest), IFloat(float))),
-        ))
-    } else {
-        Err(nom::Err::Failure(Error::new(
+        )),
+        _ => Err(nom::Err::Failure(Error::new(
             input,
-            "Cannot instantiate float",
-        )))
+            "Float literal is too large",
+        ))),
     }
 }
 
+/// Parses the `e-9` of `1e-9`. An `e` not followed by a digit or sign is left
+/// for units and identifiers.
+fn no_ws_exponent(input: Span) -> Result<Option<String>> {
+    if !input.starts_with(['e', 'E']) {
+        return Ok((input, None));
+    }
+    let rest = input.take_from(1);
+    let sign = rest.chars().next().filter(|c| matches!(c, '+' | '-'));
+    let digits = rest.take_from(sign.map_or(0, |_| 1));
+    if sign.is_none() && !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok((input, None));
+    }
+    let (rest, exponent) = required(no_ws_digits(digits, 10), digits, "Expected exponent digits")?;
+    Ok((rest, Some(format!("{}{exponent}", sign.unwrap_or('+')))))
+}
+
+fn no_ws_radix_integer<'a>(input: Span<'a>, radix: u32, name: &str) -> Result<'a, Number<'a>> {
+    let digits = input.take_from(2);
+    let (rest, value) = required(
+        no_ws_digits(digits, radix),
+        digits,
+        match radix {
+            16 => "Expected hexadecimal digits",
+            8 => "Expected octal digits",
+            _ => "Expected binary digits",
+        },
+    )?;
+    if let Some(digit) = rest.chars().next().filter(char::is_ascii_digit) {
+        return Err(nom::Err::Failure(Error::new(
+            rest,
+            format!("Invalid digit '{digit}' in {name} literal"),
+        )));
+    }
+    match i64::from_str_radix(&value, radix) {
+        Ok(int) => Ok((rest, Number::Int(Token::new(input.diff(&rest), IInt(int))))),
+        Err(_) => Err(nom::Err::Failure(Error::new(
+            input,
+            "Integer literal is too large",
+        ))),
+    }
+}
+
+fn no_ws_string(input: Span) -> Result<Str> {
+    let (mut rest, _) = char::<_, ()>('"').parse_or(input, "Expected string literal")?;
+    let mut string = String::new();
+    loop {
+        let Some(ch) = rest.chars().next() else {
+            return Err(nom::Err::Failure(Error::new(
+                input,
+                "Unterminated string literal",
+            )));
+        };
+        match ch {
+            '"' => {
+                rest = rest.take_from(1);
+                break;
+            }
+            '\\' => {
+                let escaped = match rest.chars().nth(1) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    _ => {
+                        return Err(nom::Err::Failure(Error::new(
+                            rest,
+                            "Unknown escape sequence",
+                        )));
+                    }
+                };
+                string.push(escaped);
+                rest = rest.take_from(2);
+            }
+            '\n' | '\r' => {
+                return Err(nom::Err::Failure(Error::new(
+                    input,
+                    "Unterminated string literal",
+                )));
+            }
+            ch => {
+                string.push(ch);
+                rest = rest.take_from(ch.len_utf8());
+            }
+        }
+    }
+    Ok((rest, Token::new(input.diff(&rest), IStr(string))))
+}
+
 fn no_ws_ident(input: Span) -> Result<Ident> {
     let (rest, head) = satisfy::<_, _, ()>(|c| c.is_alphabetic() || c == '_').parse_or(
         input,
         "Identifier should start with alphabetic char or underscore",
     )?;
-    let (rest, tail) = many0(satisfy::<_, _, ()>(|c| c.is_alphanumeric() || c == '_')).parse_or(
+    // Primes name derivatives, e.g. `f'` or `f'x`
+    let (rest, tail) = many0(satisfy::<_, _, ()>(|c| {
+        c.is_alphanumeric() || c == '_' || c == '\''
+    }))
+    .parse_or(
         rest,
-        "Identifier should contain only alphanumeric chars or underscore",
+        "Identifier should contain only alphanumeric chars, underscore or primes",
     )?;
     Ok((
         rest,
         Token::new(
             input.diff(&rest),
-            IIdent(iter::once(head).chain(tail.into_iter()).collect()),
+            IIdent(iter::once(head).chain(tail).collect()),
         ),
     ))
 }
 
+fn no_ws_unit_power(input: Span) -> Result<(Ident, i8)> {
+    let (rest, ident) = no_ws_ident(input)?;
+    if KEYWORDS.contains(&ident.data.0.as_str()) {
+        return Err(nom::Err::Error(Error::new(input, "Expected unit")));
+    }
+    if !rest.starts_with('^') {
+        return Ok((rest, (ident, 1)));
+    }
+    let (rest, exp) =
+        (opt(char::<_, ()>('-')), digit1).parse_or(rest.take_from(1), "Expected unit exponent")?;
+    match exp.1.parse::<i8>() {
+        Ok(value) => Ok((rest, (ident, if exp.0.is_some() { -value } else { value }))),
+        Err(_) => Err(nom::Err::Failure(Error::new(
+            exp.1,
+            "Unit exponent is out of range",
+        ))),
+    }
+}
+
+fn no_ws_unit_expr(input: Span) -> Result<UnitExpr> {
+    parsed((
+        no_ws_unit_power,
+        many0((alt((char('*'), char('/'))), no_ws_unit_power)),
+    ))
+    .map(|((head, tail), diff)| {
+        let mut units = vec![head];
+        for (op, (ident, exp)) in tail {
+            units.push((ident, if op == '/' { -exp } else { exp }));
+        }
+        Token::new(diff, IUnitExpr(units))
+    })
+    .parse(input)
+}
+
 pub fn integer(input: Span) -> Result<Int> {
     ws(no_ws_integer).parse(input)
 }
@@ -112,15 +449,66 @@ pub fn number(input: Span) -> Result<Number> {
     ws(no_ws_number).parse(input)
 }
 
+pub fn string(input: Span) -> Result<Str> {
+    ws(no_ws_string).parse(input)
+}
+
+pub fn boolean(input: Span) -> Result<Bool> {
+    let (rest, ident) = ident(input)?;
+    let value = match ident.data.0.as_str() {
+        "true" => true,
+        "false" => false,
+        _ => return Err(nom::Err::Error(Error::new(input, "Expected boolean"))),
+    };
+    Ok((rest, Token::new(ident.pos, IBool(value))))
+}
+
+/// Words with a meaning of their own, which are never taken for units.
+const KEYWORDS: &[&str] = &["let", "in", "if", "else", "while", "for"];
+
+/// Matches the reserved word `name`.
+fn keyword<'a>(name: &'static str) -> impl Parser<Span<'a>, Output = Ident<'a>, Error = Error<'a>> {
+    move |input| match ident(input) {
+        Ok((rest, ident)) if ident.data.0 == name => Ok((rest, ident)),
+        _ => Err(nom::Err::Error(Error::new(
+            input,
+            format!("Expected '{name}'"),
+        ))),
+    }
+}
+
 pub fn ident(input: Span) -> Result<Ident> {
     ws(no_ws_ident).parse(input)
 }
 
+/// Name that may be qualified with the namespaces of imports, e.g. `geo.area`.
+fn no_ws_qualified_ident(input: Span) -> Result<Ident> {
+    let (mut rest, _) = no_ws_ident(input)?;
+    while rest.starts_with('.') {
+        match no_ws_ident(rest.take_from(1)) {
+            Ok((next, _)) => rest = next,
+            Err(_) => break,
+        }
+    }
+    let name = input.diff(&rest);
+    Ok((rest, Token::new(name, IIdent(name.fragment().to_string()))))
+}
+
+pub fn qualified_ident(input: Span) -> Result<Ident> {
+    ws(no_ws_qualified_ident).parse(input)
+}
+
 pub fn func_call(input: Span) -> Result<FuncCall> {
     (
-        ident,
+        qualified_ident,
         lpar,
-        cut((opt((expression, many0((char(','), expression)))), rpar)),
+        cut((
+            opt((
+                nested_expression,
+                many0((preceded(gap, char(',')), nested_expression)),
+            )),
+            preceded(gap, rpar),
+        )),
     )
         .map(|(ident, lp, (args, rp))| {
             Token::new(
@@ -147,33 +535,107 @@ pub fn func_call(input: Span) -> Result<FuncCall> {
 }
 
 pub fn unary_operation(input: Span) -> Result<UnaryOperation> {
-    ws(parsed(alt((
-        value(IUnaryOperation::Pos, char::<_, ()>('+')),
-        value(IUnaryOperation::Neg, char::<_, ()>('-')),
-        value(IUnaryOperation::Not, char::<_, ()>('!')),
-    ))))
-    .map(|(inner, diff)| Token::new(diff, inner))
-    .parse_or(input, "Expected 'unary+', 'unary-', or 'unary!'")
+    ws(|input| {
+        parsed(alt((
+            value(IUnaryOperation::Pos, char::<_, ()>('+')),
+            value(IUnaryOperation::Neg, char::<_, ()>('-')),
+            value(IUnaryOperation::Not, char::<_, ()>('!')),
+        )))
+        .map(|(inner, diff)| Token::new(diff, inner))
+        .parse_or(input, "Expected 'unary+', 'unary-', or 'unary!'")
+    })
+    .parse(input)
+}
+
+/// Characters that user-defined operators are made of.
+const OPERATOR_CHARS: &str = "!$%&*+-/<=>@^|~";
+
+/// Symbols that user-defined operators cannot take.
+const RESERVED_OPERATORS: &[&str] = &[
+    "+", "-", "*", "/", "<", "<=", "==", "!=", ">=", ">", "=", "|", "!",
+];
+
+/// Infix operators by symbol.
+pub type Operators = HashMap<String, Fixity>;
+
+thread_local! {
+    /// Operators known to the parse in progress on this thread.
+    static OPERATORS: RefCell<Operators> = RefCell::default();
+}
+
+/// Runs `parse` with `operators` declared, and adds the operators that the
+/// parsed input defines to them.
+fn with_operators<T>(operators: &mut Operators, parse: impl FnOnce() -> T) -> T {
+    let outer = OPERATORS.replace(mem::take(operators));
+    let result = parse();
+    *operators = OPERATORS.replace(outer);
+    result
+}
+
+/// Declares `symbol` as an operator for the rest of the parse, returning
+/// the fixity it had before.
+fn declare_operator(symbol: &str, fixity: Option<Fixity>) -> Option<Fixity> {
+    OPERATORS.with_borrow_mut(|operators| match fixity {
+        Some(fixity) => operators.insert(symbol.to_string(), fixity),
+        None => operators.remove(symbol),
+    })
+}
+
+fn no_ws_operator_symbol(input: Span) -> Result<Span> {
+    let len = input
+        .chars()
+        .take_while(|c| OPERATOR_CHARS.contains(*c))
+        .map(char::len_utf8)
+        .sum();
+    if len == 0 {
+        return Err(nom::Err::Error(Error::new(input, "Expected operator")));
+    }
+    let (rest, symbol) = input.take_split(len);
+    Ok((rest, symbol))
+}
+
+/// Longest declared operator at the start of the input.
+fn no_ws_custom_operation(input: Span) -> Result<BinaryOperation> {
+    let declared = OPERATORS.with_borrow(|operators| {
+        operators
+            .iter()
+            .filter(|(symbol, _)| input.starts_with(symbol.as_str()))
+            .max_by_key(|(symbol, _)| symbol.len())
+            .map(|(symbol, fixity)| (symbol.len(), *fixity))
+    });
+    match declared {
+        Some((len, fixity)) => {
+            let (rest, symbol) = input.take_split(len);
+            Ok((rest, Token::new(symbol, IBinaryOperation::Custom(fixity))))
+        }
+        None => Err(nom::Err::Error(Error::new(input, "Expected operator"))),
+    }
 }
 
 pub fn binary_operation(input: Span) -> Result<BinaryOperation> {
-    ws(parsed(alt((
-        value(IBinaryOperation::Add, char::<_, ()>('+')),
-        value(IBinaryOperation::Sub, char('-')),
-        value(IBinaryOperation::Mul, char('*')),
-        value(IBinaryOperation::Div, char('/')),
-        value(IBinaryOperation::Lt, char('<')),
-        value(IBinaryOperation::Le, tag("<=")),
-        value(IBinaryOperation::Eq, tag("==")),
-        value(IBinaryOperation::Ne, tag("!=")),
-        value(IBinaryOperation::Ge, tag(">=")),
-        value(IBinaryOperation::Gt, char('>')),
-    ))))
-    .map(|(inner, diff)| Token::new(diff, inner))
-    .parse_or(
-        input,
-        "Expected '+', '-', '*', '/', '<', '<=', '==', '!=', '>=', '>'",
-    )
+    ws(|input| {
+        if let Ok(result) = no_ws_custom_operation(input) {
+            return Ok(result);
+        }
+        parsed(alt((
+            value(IBinaryOperation::Add, char::<_, ()>('+')),
+            value(IBinaryOperation::Sub, char('-')),
+            value(IBinaryOperation::Mul, char('*')),
+            value(IBinaryOperation::Div, char('/')),
+            value(IBinaryOperation::Le, tag("<=")),
+            value(IBinaryOperation::Lt, char('<')),
+            value(IBinaryOperation::Eq, tag("==")),
+            value(IBinaryOperation::Ne, tag("!=")),
+            value(IBinaryOperation::Ge, tag(">=")),
+            value(IBinaryOperation::Gt, char('>')),
+        )))
+        .map(|(inner, diff)| Token::new(diff, inner))
+        .parse_or(
+            input,
+            "Expected '+', '-', '*', '/', '<', '<=', '==', '!=', '>=', '>'",
+        )
+    })
+    .parse(input)
 }
 
 struct ExpressionTokens<'a> {
@@ -183,11 +645,24 @@ struct ExpressionTokens<'a> {
 
 impl<'a> ExpressionTokens<'a> {
     fn simplify(mut self, input: Span<'a>) -> ExpressionTokens<'a> {
-        while let Some(pos) = self
+        // The strongest operator binds first: of several equally strong ones
+        // the leftmost, unless they group to the right
+        while let Some(precedence) = self
             .operations
             .iter()
-            .position(|op| matches!(*op.data, IBinaryOperation::Mul | IBinaryOperation::Div))
+            .map(|op| op.data.fixity().precedence)
+            .max()
         {
+            let mut strongest = self
+                .operations
+                .iter()
+                .enumerate()
+                .filter(|(_, op)| op.data.fixity().precedence == precedence);
+            let (first, op) = strongest.next().unwrap();
+            let pos = match op.data.fixity().associativity {
+                Associativity::Left => first,
+                Associativity::Right => strongest.next_back().map_or(first, |(last, _)| last),
+            };
             let op = self.operations.remove(pos);
             let rhs = self.operands.remove(pos + 1);
             let lhs = self.operands[pos].clone();
@@ -198,17 +673,6 @@ impl<'a> ExpressionTokens<'a> {
                 IExpression::Binary(lhs, op, rhs),
             );
         }
-        while !self.operations.is_empty() {
-            let op = self.operations.remove(0);
-            let rhs = self.operands.remove(1);
-            let lhs = self.operands[0].clone();
-            self.operands[0] = Token::new(
-                input
-                    .take_from(input.offset(&lhs.pos))
-                    .including_diff(&rhs.pos),
-                IExpression::Binary(lhs, op, rhs),
-            );
-        }
         self
     }
 
@@ -228,10 +692,16 @@ enum ParseExpressionTokensRest<'a> {
     ),
 }
 
-fn parse_expression_tokens(input: Span) -> Result<ExpressionTokens> {
+/// Parses the operands and operators of an expression. A `nested` expression
+/// is inside brackets, so it may also continue on the next line before an
+/// operator; any expression may continue after one.
+fn parse_expression_tokens(input: Span, nested: bool) -> Result<ExpressionTokens> {
+    let lead = move |input| if nested { gap(input) } else { Ok((input, ())) };
+    let operand = move |input| parse_expression_tokens(input, nested);
+    let value = move |input| expression_in(input, nested);
     (
         alt((
-            (unary_operation, cut(parse_expression_tokens)).map(|(op, mut exp)| {
+            (unary_operation, cut(operand)).map(|(op, mut exp)| {
                 let exp0 = exp.operands[0].clone();
                 exp.operands[0] = Token::new(
                     input
@@ -244,44 +714,127 @@ fn parse_expression_tokens(input: Span) -> Result<ExpressionTokens> {
                     operations: vec![],
                 }
             }),
-            (lpar, cut((parse_expression_tokens, rpar))).map(|(lp, (exp, rp))| {
-                let exp = exp.simplify(input).operands.remove(0);
-                ExpressionTokens {
+            (
+                lpar,
+                cut((
+                    preceded(gap, |input| parse_expression_tokens(input, true)),
+                    preceded(gap, rpar),
+                )),
+            )
+                .map(|(lp, (exp, rp))| {
+                    let exp = exp.simplify(input).operands.remove(0);
+                    ExpressionTokens {
+                        operands: vec![Token::new(
+                            input
+                                .take_from(input.offset(&lp.pos))
+                                .including_diff(&rp.pos),
+                            exp.data.clone(),
+                        )],
+                        operations: vec![],
+                    }
+                }),
+            boolean.map(|bool| ExpressionTokens {
+                operands: vec![Token::new(bool.pos, IExpression::Bool(bool))],
+                operations: vec![],
+            }),
+            (
+                keyword("let"),
+                cut((
+                    separated_list1(
+                        preceded(gap, char(',')),
+                        (preceded(gap, ident), assign_eql, cut(preceded(gap, value))),
+                    ),
+                    preceded(gap, keyword("in")),
+                    preceded(gap, value),
+                )),
+            )
+                .map(|(kw, (bindings, _, body))| ExpressionTokens {
                     operands: vec![Token::new(
                         input
-                            .take_from(input.offset(&lp.pos))
-                            .including_diff(&rp.pos),
-                        exp.data.clone(),
+                            .take_from(input.offset(&kw.pos))
+                            .including_diff(&body.pos),
+                        IExpression::Let(
+                            bindings
+                                .into_iter()
+                                .map(|(ident, _, value)| (ident, value))
+                                .collect(),
+                            body,
+                        ),
                     )],
                     operations: vec![],
-                }
-            }),
+                }),
+            (
+                lbrk,
+                cut((
+                    alt((
+                        (
+                            nested_expression,
+                            preceded(gap, ws(tag(".."))),
+                            nested_expression,
+                        )
+                            .map(|(lo, _, hi)| IExpression::Interval(lo, hi)),
+                        opt((
+                            nested_expression,
+                            many0((preceded(gap, char(',')), nested_expression)),
+                        ))
+                        .map(|items| {
+                            IExpression::List(match items {
+                                Some((item0, items)) => iter::once(item0)
+                                    .chain(items.into_iter().map(|(_, item)| item))
+                                    .collect::<Vec<_>>(),
+                                None => vec![],
+                            })
+                        }),
+                    )),
+                    preceded(gap, rbrk),
+                )),
+            )
+                .map(|(lb, (expr, rb))| ExpressionTokens {
+                    operands: vec![Token::new(
+                        input
+                            .take_from(input.offset(&lb.pos))
+                            .including_diff(&rb.pos),
+                        expr,
+                    )],
+                    operations: vec![],
+                }),
             func_call.map(|call| ExpressionTokens {
                 operands: vec![Token::new(call.pos, IExpression::Call(call))],
                 operations: vec![],
             }),
-            ident.map(|id| ExpressionTokens {
+            qualified_ident.map(|id| ExpressionTokens {
                 operands: vec![Token::new(id.pos, IExpression::Ident(id))],
                 operations: vec![],
             }),
-            number.map(|num| ExpressionTokens {
+            string.map(|string| ExpressionTokens {
+                operands: vec![Token::new(string.pos, IExpression::Str(string))],
+                operations: vec![],
+            }),
+            (number, no_ws_unit_expr, blank).map(|(num, unit, _)| ExpressionTokens {
                 operands: vec![Token::new(
-                    match &num {
-                        Number::Int(token) => token.pos,
-                        Number::Float(token) => token.pos,
-                    },
-                    IExpression::Number(num),
+                    input
+                        .take_from(input.offset(&num.pos()))
+                        .including_diff(&unit.pos),
+                    IExpression::Quantity(num, unit),
                 )],
                 operations: vec![],
             }),
+            number.map(|num| ExpressionTokens {
+                operands: vec![Token::new(num.pos(), IExpression::Number(num))],
+                operations: vec![],
+            }),
         )),
         alt((
             (
-                que,
-                cut((parse_expression_tokens, col, parse_expression_tokens)),
+                preceded(lead, que),
+                cut((
+                    preceded(gap, operand),
+                    preceded(lead, col),
+                    preceded(gap, operand),
+                )),
             )
                 .map(ParseExpressionTokensRest::Ternary),
-            many0((binary_operation, parse_expression_tokens))
+            many0((preceded(lead, binary_operation), preceded(gap, operand)))
                 .map(ParseExpressionTokensRest::Regular),
         )),
     )
@@ -310,44 +863,145 @@ fn parse_expression_tokens(input: Span) -> Result<ExpressionTokens> {
 }
 
 pub fn expression(input: Span) -> Result<Expression> {
-    parse_expression_tokens
+    expression_in(input, false)
+}
+
+fn expression_in(input: Span, nested: bool) -> Result<Expression> {
+    (|input| parse_expression_tokens(input, nested))
         .map(|tok| tok.simplify(input).operands.remove(0))
         .parse(input)
 }
 
+/// Expression inside brackets, which may span several lines.
+fn nested_expression(input: Span) -> Result<Expression> {
+    preceded(gap, |input| expression_in(input, true)).parse(input)
+}
+
+/// Matches a lone `=` so that `x == y` is not mistaken for an assignment.
+fn assign_eql(input: Span) -> Result<Eql> {
+    terminated(eql, not(char('='))).parse(input)
+}
+
 pub fn var_assign(input: Span) -> Result<VarAssign> {
+    var_assign_until(input, end)
+}
+
+fn var_assign_until(input: Span, end: Terminator) -> Result<VarAssign> {
+    let (rest, constant) = match keyword("const").parse(input) {
+        // `const = ...` assigns a variable named `const`
+        Ok((rest, kw)) if ident(rest).is_ok() => (rest, Some(kw)),
+        _ => (input, None),
+    };
     (
         ident,
-        eql,
+        assign_eql,
         cut((
             alt((
                 expression.map(VarAssignExpr::Expression),
                 que.map(VarAssignExpr::UserInput),
             )),
-            eol,
+            end,
         )),
     )
-        .map(|(ident, _, (expr, eol))| {
+        .map(|(ident, _, (expr, end))| {
+            let head = constant.as_ref().map_or(ident.pos, |kw| kw.pos);
             Token::new(
-                input.take_from(input.offset(&ident.pos)).diff(&eol.pos),
-                IVarAssign { ident, expr },
+                input.take_from(input.offset(&head)).diff(&end),
+                IVarAssign {
+                    ident,
+                    expr,
+                    constant: constant.is_some(),
+                },
             )
         })
-        .parse(input)
+        .parse(rest)
 }
 
 pub fn func_assign(input: Span) -> Result<FuncAssign> {
+    func_assign_until(input, end)
+}
+
+/// Parses an operator definition like `infixl 6 <+> (a, b) = a + b`, which
+/// declares the operator for its own body and the lines after it.
+fn operator_until(input: Span, end: Terminator) -> Result<FuncAssign> {
+    let (rest, (kw, precedence)) =
+        (alt((keyword("infixl"), keyword("infixr"))), ws(digit1)).parse(input)?;
+    let precedence = match precedence.parse::<u8>() {
+        Ok(precedence @ 0..=9) => precedence,
+        _ => {
+            return Err(nom::Err::Failure(Error::new(
+                precedence,
+                "Operator precedence must be 0-9",
+            )));
+        }
+    };
+    let associativity = match kw.data.0.as_str() {
+        "infixl" => Associativity::Left,
+        _ => Associativity::Right,
+    };
+    let (rest, symbol) = cut(ws(no_ws_operator_symbol)).parse(rest)?;
+    if RESERVED_OPERATORS.contains(symbol.fragment()) {
+        return Err(nom::Err::Failure(Error::new(
+            symbol,
+            format!("Cannot redefine the operator '{symbol}'"),
+        )));
+    }
+    // The body may already use the operator, which stays declared only if
+    // the whole definition parses
+    let fixity = Fixity {
+        precedence,
+        associativity,
+    };
+    let previous = declare_operator(&symbol, Some(fixity));
+    let parsed = cut((
+        lpar,
+        separated_list0(char(','), ident),
+        rpar,
+        assign_eql,
+        (expression, end),
+    ))
+    .parse(rest);
+    if !matches!(&parsed, Ok((_, (_, args, ..))) if args.len() == 2) {
+        declare_operator(&symbol, previous);
+    }
+    let (rest, (lp, args, rp, _, (expr, end))) = parsed?;
+    if args.len() != 2 {
+        return Err(nom::Err::Failure(Error::new(
+            lp.pos,
+            format!("Operator '{symbol}' must take 2 arguments"),
+        )));
+    }
+    Ok((
+        rest,
+        Token::new(
+            input.take_from(input.offset(&kw.pos)).diff(&end),
+            IFuncAssign {
+                ident: Token::new(symbol, IIdent(symbol.to_string())),
+                args: Token::new(
+                    input
+                        .take_from(input.offset(&lp.pos))
+                        .including_diff(&rp.pos),
+                    IFuncAssignArgs(args),
+                ),
+                expr,
+                memo: false,
+            },
+        ),
+    ))
+}
+
+fn func_assign_until(input: Span, end: Terminator) -> Result<FuncAssign> {
     (
         ident,
         lpar,
         opt((ident, many0((char(','), ident)))),
         rpar,
-        eql,
-        cut((expression, eol)),
+        assign_eql,
+        cut((expression, end)),
     )
-        .map(|(ident, lp, args, rp, _, (exp, eol))| {
+        .map(|(ident, lp, args, rp, _, (exp, end))| {
             Token::new(
-                input.take_from(input.offset(&ident.pos)).diff(&eol.pos),
+                input.take_from(input.offset(&ident.pos)).diff(&end),
                 IFuncAssign {
                     ident,
                     args: Token::new(
@@ -362,24 +1016,337 @@ pub fn func_assign(input: Span) -> Result<FuncAssign> {
                         }),
                     ),
                     expr: exp,
+                    memo: false,
+                },
+            )
+        })
+        .parse(input)
+}
+
+/// Literal that a clause parameter can match: a number, possibly signed, a
+/// string or a boolean.
+fn is_literal(expr: &Expression) -> bool {
+    match expr.data.as_ref() {
+        IExpression::Number(_) | IExpression::Str(_) | IExpression::Bool(_) => true,
+        IExpression::Unary(expr, op) => *op.data != IUnaryOperation::Not && is_literal(expr),
+        _ => false,
+    }
+}
+
+fn param(input: Span) -> Result<Param> {
+    let (rest, expr) = expression(input)?;
+    match expr.data.as_ref() {
+        IExpression::Ident(ident) => Ok((rest, Param::Ident(ident.clone()))),
+        _ if is_literal(&expr) => Ok((rest, Param::Literal(expr))),
+        _ => Err(nom::Err::Error(Error::new(
+            expr.pos,
+            "Expected parameter name or literal",
+        ))),
+    }
+}
+
+/// `f(params) = expr` or `f(params) | guard = expr | otherwise = expr`, with
+/// the guards possibly on lines of their own. Returns the function name and
+/// the span of the parameter list along with the clause.
+fn clause(input: Span) -> Result<(Ident, Span, Clause)> {
+    let guard = alt((keyword("otherwise").map(|_| None), expression.map(Some)));
+    let guarded = many1(preceded(
+        (gap, ws(char('|'))),
+        cut((guard, assign_eql, expression)),
+    ))
+    .map(|guards| {
+        guards
+            .into_iter()
+            .map(|(guard, _, expr)| (guard, expr))
+            .collect()
+    });
+    let plain = preceded(assign_eql, cut(expression)).map(|expr| vec![(None, expr)]);
+    (
+        ident,
+        lpar,
+        separated_list0(char(','), param),
+        rpar,
+        alt((guarded, plain)),
+    )
+        .map(|(ident, lp, params, rp, guards): (_, _, _, _, Vec<_>)| {
+            let last = &guards[guards.len() - 1].1;
+            let pos = input
+                .take_from(input.offset(&ident.pos))
+                .including_diff(&last.pos);
+            let args = input
+                .take_from(input.offset(&lp.pos))
+                .including_diff(&rp.pos);
+            (ident, args, Token::new(pos, IClause { params, guards }))
+        })
+        .parse(input)
+}
+
+/// A function definition, which is a plain [`FuncAssign`] unless it has
+/// literal parameters, guards or several clauses separated by `;`. It may
+/// start with `memo`.
+fn definition_until(input: Span, end: Terminator) -> Result<Program> {
+    let (rest, memo) = match keyword("memo").parse(input) {
+        // `memo(x) = ...` defines a function named `memo`
+        Ok((rest, kw)) if ident(rest).is_ok() => (rest, Some(kw)),
+        _ => (input, None),
+    };
+    let (mut rest, (ident, args, first)) = clause(rest)?;
+    let mut clauses = vec![first];
+    while let Ok((next, _)) = ws(char::<_, Error>(';')).parse(rest) {
+        let (next, (other, _, clause)) = preceded(gap, cut(clause)).parse(next)?;
+        if other.data.0 != ident.data.0 {
+            return Err(nom::Err::Failure(Error::new(
+                other.pos,
+                format!("Expected another clause of '{}'", ident.data.0),
+            )));
+        }
+        if clause.data.params.len() != clauses[0].data.params.len() {
+            return Err(nom::Err::Failure(Error::new(
+                other.pos,
+                format!(
+                    "Clauses of '{}' must take {} arguments",
+                    ident.data.0,
+                    clauses[0].data.params.len()
+                ),
+            )));
+        }
+        clauses.push(clause);
+        rest = next;
+    }
+    let (rest, end) = cut(end).parse(rest)?;
+    let head = memo.as_ref().map_or(ident.pos, |kw| kw.pos);
+    let pos = input.take_from(input.offset(&head)).diff(&end);
+    let memo = memo.is_some();
+    let plain = match clauses.as_slice() {
+        [clause] => match clause.data.guards.as_slice() {
+            [(None, expr)] => clause
+                .data
+                .params
+                .iter()
+                .map(|param| match param {
+                    Param::Ident(ident) => Some(ident.clone()),
+                    Param::Literal(_) => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(|params| (params, expr.clone())),
+            _ => None,
+        },
+        _ => None,
+    };
+    let program = match plain {
+        Some((params, expr)) => Program::Func(Token::new(
+            pos,
+            IFuncAssign {
+                ident,
+                args: Token::new(args, IFuncAssignArgs(params)),
+                expr,
+                memo,
+            },
+        )),
+        None => Program::Piecewise(Token::new(
+            pos,
+            IPiecewise {
+                ident,
+                clauses,
+                memo,
+            },
+        )),
+    };
+    Ok((rest, program))
+}
+
+pub fn block(input: Span) -> Result<Block> {
+    let blank_lines = |input| many0((space0, eol)).parse(input);
+    (
+        lbrc,
+        cut((
+            many0(preceded(blank_lines, |input| {
+                program_until(input, block_end)
+            })),
+            blank_lines,
+            rbrc,
+        )),
+    )
+        .map(|(lb, (programs, _, rb))| {
+            Token::new(
+                input
+                    .take_from(input.offset(&lb.pos))
+                    .including_diff(&rb.pos),
+                IBlock(programs),
+            )
+        })
+        .parse(input)
+}
+
+pub fn if_statement(input: Span) -> Result<If> {
+    (
+        keyword("if"),
+        cut((
+            expression,
+            block,
+            opt(preceded(
+                (gap, keyword("else")),
+                cut(alt((
+                    block,
+                    if_statement.map(|stmt| Token::new(stmt.pos, IBlock(vec![Program::If(stmt)]))),
+                ))),
+            )),
+        )),
+    )
+        .map(|(kw, (cond, then, otherwise))| {
+            let last = otherwise.as_ref().unwrap_or(&then);
+            Token::new(
+                input
+                    .take_from(input.offset(&kw.pos))
+                    .including_diff(&last.pos),
+                IIf {
+                    cond,
+                    then,
+                    otherwise,
                 },
             )
         })
         .parse(input)
 }
 
-#[derive(Debug)]
+pub fn while_statement(input: Span) -> Result<While> {
+    (keyword("while"), cut((expression, block)))
+        .map(|(kw, (cond, body))| {
+            Token::new(
+                input
+                    .take_from(input.offset(&kw.pos))
+                    .including_diff(&body.pos),
+                IWhile { cond, body },
+            )
+        })
+        .parse(input)
+}
+
+pub fn for_statement(input: Span) -> Result<For> {
+    (
+        keyword("for"),
+        cut((
+            ident,
+            keyword("in"),
+            alt((
+                (expression, ws(tag("..")), expression).map(|(lo, _, hi)| ForIter::Range(lo, hi)),
+                expression.map(ForIter::List),
+            )),
+            block,
+        )),
+    )
+        .map(|(kw, (var, _, iter, body))| {
+            Token::new(
+                input
+                    .take_from(input.offset(&kw.pos))
+                    .including_diff(&body.pos),
+                IFor { var, iter, body },
+            )
+        })
+        .parse(input)
+}
+
+pub fn import(input: Span) -> Result<Import> {
+    (
+        keyword("import"),
+        cut((string, opt(preceded(keyword("as"), cut(ident))))),
+    )
+        .map(|(kw, (path, alias))| {
+            let last = alias.as_ref().map_or(path.pos, |alias| alias.pos);
+            Token::new(
+                input.take_from(input.offset(&kw.pos)).including_diff(&last),
+                IImport { path, alias },
+            )
+        })
+        .parse(input)
+}
+
+#[derive(Debug, Clone)]
 pub enum Program<'a> {
     Expression(Expression<'a>),
     Func(FuncAssign<'a>),
     Var(VarAssign<'a>),
+    /// A function defined by literal patterns or guards.
+    Piecewise(Piecewise<'a>),
+    /// A comment on a line of its own or after a statement.
+    Comment(Comment<'a>),
+    If(If<'a>),
+    While(While<'a>),
+    For(For<'a>),
+    Import(Import<'a>),
+}
+
+impl<'a> Program<'a> {
+    /// Source of the statement.
+    pub fn pos(&self) -> Span<'a> {
+        match self {
+            Program::Expression(token) => token.pos,
+            Program::Func(token) => token.pos,
+            Program::Var(token) => token.pos,
+            Program::Piecewise(token) => token.pos,
+            Program::Comment(token) => token.pos,
+            Program::If(token) => token.pos,
+            Program::While(token) => token.pos,
+            Program::For(token) => token.pos,
+            Program::Import(token) => token.pos,
+        }
+    }
 }
 
+/// Parses a single statement. Operators it defines are forgotten after it;
+/// see [`program_with`] to keep them.
 pub fn program(input: Span) -> Result<Program> {
+    program_with(input, &mut Operators::new())
+}
+
+/// Parses a single statement knowing `operators`, and adds the operators it
+/// defines to them.
+pub fn program_with<'a>(input: Span<'a>, operators: &mut Operators) -> Result<'a, Program<'a>> {
+    with_operators(operators, || program_until(input, end))
+}
+
+fn program_until(input: Span, end: Terminator) -> Result<Program> {
     alt((
-        var_assign.map(Program::Var),
-        func_assign.map(Program::Func),
-        (expression, eol).map(|(expr, _)| Program::Expression(expr)),
+        // Tried first, so that the error reported is that of the last branch
+        (comment, end).map(|(comment, _)| Program::Comment(comment)),
+        (if_statement, end).map(|(stmt, _)| Program::If(stmt)),
+        (while_statement, end).map(|(stmt, _)| Program::While(stmt)),
+        (for_statement, end).map(|(stmt, _)| Program::For(stmt)),
+        (|input| operator_until(input, end)).map(Program::Func),
+        (|input| var_assign_until(input, end)).map(Program::Var),
+        |input| definition_until(input, end),
+        (import, end).map(|(import, _)| Program::Import(import)),
+        (expression, end).map(|(expr, _)| Program::Expression(expr)),
     ))
     .parse(input)
 }
+
+/// Parses a whole script. Unlike a sequence of [`program`]s, it allows blank
+/// lines and a missing newline at the end. Operators it defines are known to
+/// the lines after them.
+pub fn script(input: Span) -> Result<Vec<Program>> {
+    script_with(input, &mut Operators::new())
+}
+
+/// Parses a whole script knowing `operators`, and adds the operators it
+/// defines to them.
+pub fn script_with<'a>(input: Span<'a>, operators: &mut Operators) -> Result<'a, Vec<Program<'a>>> {
+    with_operators(operators, || {
+        let mut programs = vec![];
+        let (mut rest, _) = blank_lines(input)?;
+        while !rest.is_empty() {
+            let (next, program) = program_until(rest, end_or_eof)?;
+            programs.push(program);
+            (rest, _) = blank_lines(next)?;
+        }
+        Ok((rest, programs))
+    })
+}
+
+/// Skips lines holding nothing but whitespace, including a last one without
+/// a newline.
+fn blank_lines(input: Span) -> Result<()> {
+    (many0((space0, eol)), opt((space0, eof)))
+        .map(|_| ())
+        .parse(input)
+}
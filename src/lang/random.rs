@@ -0,0 +1,56 @@
+use std::f64::consts::TAU;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SplitMix64 pseudo-random generator: small, fast and fully determined by its seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in `[0, bound)`, without modulo bias.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Normally distributed number, by the Box-Muller transform.
+    pub fn normal(&mut self, mu: f64, sigma: f64) -> f64 {
+        let u1 = 1. - self.next_f64();
+        let u2 = self.next_f64();
+        mu + sigma * (-2. * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+}
+
+impl Default for Rng {
+    /// Seeds from the clock, for when reproducibility is not needed.
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new(nanos)
+    }
+}
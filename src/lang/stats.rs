@@ -0,0 +1,51 @@
+/// Arithmetic mean. Like the other aggregates, expects a non-empty sample.
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Population variance.
+pub fn variance(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    percentile(values, 50.)
+}
+
+/// Percentile `p` (between 0 and 100) with linear interpolation between the
+/// closest ranks.
+pub fn percentile(values: &[f64], p: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = p / 100. * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+/// Most bins [`histogram`] may be asked for.
+pub const MAX_BINS: usize = 10_000;
+
+/// Counts values in `bins` equal-width bins spanning the sample range.
+/// Expects `bins` between 1 and [`MAX_BINS`].
+pub fn histogram(values: &[f64], bins: usize) -> Vec<usize> {
+    assert!((1..=MAX_BINS).contains(&bins), "Invalid number of bins");
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let width = (max - min) / bins as f64;
+    let mut counts = vec![0; bins];
+    for value in values {
+        let bin = if width > 0. {
+            ((value - min) / width) as usize
+        } else {
+            0
+        };
+        // The maximum belongs to the last bin
+        counts[bin.min(bins - 1)] += 1;
+    }
+    counts
+}
@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::interpret::Func;
+use super::tokens::{Expression, IBinaryOperation, IExpression, IUnaryOperation, Number};
+
+/// Expression tree detached from the source text, used to derive and
+/// simplify functions symbolically.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sym {
+    Num(f64),
+    Bool(bool),
+    Var(String),
+    Unary(IUnaryOperation, Box<Sym>),
+    Binary(Box<Sym>, IBinaryOperation, Box<Sym>),
+    Call(String, Vec<Sym>),
+    Ternary(Box<Sym>, Box<Sym>, Box<Sym>),
+}
+
+#[derive(Debug)]
+pub enum DeriveError {
+    UndefinedFunction(String),
+    NotCustomFunction(String),
+    UndefinedArgument(String, String),
+    RecursiveFunction(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for DeriveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeriveError::UndefinedFunction(name) => write!(f, "Undefined function: '{name}'"),
+            DeriveError::NotCustomFunction(name) => {
+                write!(
+                    f,
+                    "Only user-defined functions can be derived, '{name}' is built-in"
+                )
+            }
+            DeriveError::UndefinedArgument(name, var) => {
+                write!(f, "Function '{name}' has no argument '{var}'")
+            }
+            DeriveError::RecursiveFunction(name) => {
+                write!(f, "Cannot derive recursive function '{name}'")
+            }
+            DeriveError::Unsupported(expr) => {
+                write!(f, "Cannot derive '{expr}' symbolically")
+            }
+        }
+    }
+}
+
+impl Sym {
+    pub fn from_expression(expr: &Expression) -> Result<Sym, DeriveError> {
+        let unsupported = || DeriveError::Unsupported(expr.to_string());
+        Ok(match expr.data.as_ref() {
+            IExpression::Number(Number::Int(token)) => Sym::Num(token.data.0 as f64),
+            IExpression::Number(Number::Float(token)) => Sym::Num(token.data.0),
+            IExpression::Bool(token) => Sym::Bool(token.data.0),
+            IExpression::Ident(token) => Sym::Var(token.data.0.clone()),
+            IExpression::Unary(expr, op) => match op.data.as_ref() {
+                IUnaryOperation::Pos => Sym::from_expression(expr)?,
+                op => Sym::Unary(*op, Box::new(Sym::from_expression(expr)?)),
+            },
+            // User-defined operators are calls of the function they name
+            IExpression::Binary(lhs, op, rhs)
+                if matches!(*op.data, IBinaryOperation::Custom(_)) =>
+            {
+                Sym::Call(
+                    op.pos.fragment().to_string(),
+                    vec![Sym::from_expression(lhs)?, Sym::from_expression(rhs)?],
+                )
+            }
+            IExpression::Binary(lhs, op, rhs) => Sym::Binary(
+                Box::new(Sym::from_expression(lhs)?),
+                *op.data,
+                Box::new(Sym::from_expression(rhs)?),
+            ),
+            IExpression::Ternary(cnd, lhs, rhs) => Sym::Ternary(
+                Box::new(Sym::from_expression(cnd)?),
+                Box::new(Sym::from_expression(lhs)?),
+                Box::new(Sym::from_expression(rhs)?),
+            ),
+            IExpression::Call(call) => Sym::Call(
+                call.data.ident.data.0.clone(),
+                call.data
+                    .args
+                    .data
+                    .0
+                    .iter()
+                    .map(Sym::from_expression)
+                    .collect::<Result<_, _>>()?,
+            ),
+            IExpression::Let(bindings, body) => {
+                // Bindings are inlined, each seeing the ones before it
+                let mut scope: HashMap<&str, Sym> = HashMap::new();
+                for (ident, value) in bindings {
+                    let value = Sym::from_expression(value)?;
+                    let value = value.substitute(&scope.iter().map(|(k, v)| (*k, v)).collect());
+                    scope.insert(&ident.data.0, value);
+                }
+                Sym::from_expression(body)?
+                    .substitute(&scope.iter().map(|(k, v)| (*k, v)).collect())
+            }
+            IExpression::Number(Number::Imaginary(_))
+            | IExpression::Quantity(..)
+            | IExpression::Str(_)
+            | IExpression::Interval(..)
+            | IExpression::List(_) => return Err(unsupported()),
+        })
+    }
+
+    fn depends_on(&self, var: &str) -> bool {
+        match self {
+            Sym::Num(_) | Sym::Bool(_) => false,
+            Sym::Var(name) => name == var,
+            Sym::Unary(_, expr) => expr.depends_on(var),
+            Sym::Binary(lhs, _, rhs) => lhs.depends_on(var) || rhs.depends_on(var),
+            Sym::Call(_, args) => args.iter().any(|arg| arg.depends_on(var)),
+            Sym::Ternary(cnd, lhs, rhs) => {
+                cnd.depends_on(var) || lhs.depends_on(var) || rhs.depends_on(var)
+            }
+        }
+    }
+
+    /// Replaces variables simultaneously according to `bindings`.
+    fn substitute(&self, bindings: &HashMap<&str, &Sym>) -> Sym {
+        match self {
+            Sym::Var(name) => match bindings.get(name.as_str()) {
+                Some(sym) => (*sym).clone(),
+                None => self.clone(),
+            },
+            Sym::Num(_) | Sym::Bool(_) => self.clone(),
+            Sym::Unary(op, expr) => Sym::Unary(*op, Box::new(expr.substitute(bindings))),
+            Sym::Binary(lhs, op, rhs) => Sym::Binary(
+                Box::new(lhs.substitute(bindings)),
+                *op,
+                Box::new(rhs.substitute(bindings)),
+            ),
+            Sym::Call(name, args) => Sym::Call(
+                name.clone(),
+                args.iter().map(|arg| arg.substitute(bindings)).collect(),
+            ),
+            Sym::Ternary(cnd, lhs, rhs) => Sym::Ternary(
+                Box::new(cnd.substitute(bindings)),
+                Box::new(lhs.substitute(bindings)),
+                Box::new(rhs.substitute(bindings)),
+            ),
+        }
+    }
+
+    /// Derivative with respect to `var`. Calls to user-defined functions are
+    /// differentiated through their bodies; `stack` holds the functions being
+    /// derived to reject recursion.
+    pub fn derivative(
+        &self,
+        var: &str,
+        funcs: &HashMap<String, Func>,
+        stack: &mut Vec<String>,
+    ) -> Result<Sym, DeriveError> {
+        if !self.depends_on(var) {
+            return Ok(Sym::Num(0.));
+        }
+        Ok(match self {
+            Sym::Num(_) | Sym::Bool(_) => Sym::Num(0.),
+            Sym::Var(_) => Sym::Num(1.),
+            Sym::Unary(IUnaryOperation::Neg, expr) => neg(expr.derivative(var, funcs, stack)?),
+            Sym::Unary(..) => Sym::Num(0.),
+            Sym::Binary(lhs, op, rhs) => {
+                let (u, v) = (lhs.as_ref().clone(), rhs.as_ref().clone());
+                match op {
+                    IBinaryOperation::Add | IBinaryOperation::Sub => binary(
+                        lhs.derivative(var, funcs, stack)?,
+                        *op,
+                        rhs.derivative(var, funcs, stack)?,
+                    ),
+                    IBinaryOperation::Mul => binary(
+                        binary(lhs.derivative(var, funcs, stack)?, IBinaryOperation::Mul, v),
+                        IBinaryOperation::Add,
+                        binary(u, IBinaryOperation::Mul, rhs.derivative(var, funcs, stack)?),
+                    ),
+                    IBinaryOperation::Div if !rhs.depends_on(var) => {
+                        binary(lhs.derivative(var, funcs, stack)?, IBinaryOperation::Div, v)
+                    }
+                    IBinaryOperation::Div => binary(
+                        binary(
+                            binary(
+                                lhs.derivative(var, funcs, stack)?,
+                                IBinaryOperation::Mul,
+                                v.clone(),
+                            ),
+                            IBinaryOperation::Sub,
+                            binary(u, IBinaryOperation::Mul, rhs.derivative(var, funcs, stack)?),
+                        ),
+                        IBinaryOperation::Div,
+                        binary(v.clone(), IBinaryOperation::Mul, v),
+                    ),
+                    // Comparisons are piecewise constant
+                    _ => Sym::Num(0.),
+                }
+            }
+            Sym::Ternary(cnd, lhs, rhs) => Sym::Ternary(
+                cnd.clone(),
+                Box::new(lhs.derivative(var, funcs, stack)?),
+                Box::new(rhs.derivative(var, funcs, stack)?),
+            ),
+            Sym::Call(name, args) => match funcs.get(name) {
+                Some(Func::Custom(func)) => {
+                    if stack.contains(name) {
+                        return Err(DeriveError::RecursiveFunction(name.clone()));
+                    }
+                    let params = &func.data.args.data.0;
+                    if params.len() != args.len() {
+                        return Err(DeriveError::Unsupported(self.to_string()));
+                    }
+                    stack.push(name.clone());
+                    let body = Sym::from_expression(&func.data.expr)?;
+                    let bindings: HashMap<&str, &Sym> = params
+                        .iter()
+                        .map(|param| param.data.0.as_str())
+                        .zip(args)
+                        .collect();
+                    // Chain rule over every argument
+                    let mut result = Sym::Num(0.);
+                    for (param, arg) in params.iter().zip(args) {
+                        if !arg.depends_on(var) {
+                            continue;
+                        }
+                        let partial = body
+                            .derivative(&param.data.0, funcs, stack)?
+                            .substitute(&bindings);
+                        result = binary(
+                            result,
+                            IBinaryOperation::Add,
+                            binary(
+                                partial,
+                                IBinaryOperation::Mul,
+                                arg.derivative(var, funcs, stack)?,
+                            ),
+                        );
+                    }
+                    stack.pop();
+                    result
+                }
+                _ => {
+                    let [arg] = args.as_slice() else {
+                        return Err(DeriveError::Unsupported(self.to_string()));
+                    };
+                    let call = |name: &str, arg: Sym| Sym::Call(name.to_string(), vec![arg]);
+                    let outer = match name.as_str() {
+                        "sqrt" => binary(
+                            Sym::Num(1.),
+                            IBinaryOperation::Div,
+                            binary(Sym::Num(2.), IBinaryOperation::Mul, self.clone()),
+                        ),
+                        "exp" => self.clone(),
+                        "ln" => binary(Sym::Num(1.), IBinaryOperation::Div, arg.clone()),
+                        "sin" => call("cos", arg.clone()),
+                        "cos" => neg(call("sin", arg.clone())),
+                        "abs" => binary(arg.clone(), IBinaryOperation::Div, self.clone()),
+                        _ => return Err(DeriveError::Unsupported(self.to_string())),
+                    };
+                    binary(
+                        outer,
+                        IBinaryOperation::Mul,
+                        arg.derivative(var, funcs, stack)?,
+                    )
+                }
+            },
+        })
+    }
+
+    /// Rewrites the expression until no simplification applies.
+    pub fn simplify(self) -> Sym {
+        let mut sym = self;
+        for _ in 0..64 {
+            let next = sym.simplify_once();
+            if next == sym {
+                break;
+            }
+            sym = next;
+        }
+        sym
+    }
+
+    fn simplify_once(&self) -> Sym {
+        match self {
+            Sym::Num(_) | Sym::Bool(_) | Sym::Var(_) => self.clone(),
+            Sym::Unary(op, expr) => match (op, expr.simplify_once()) {
+                (IUnaryOperation::Neg, Sym::Num(number)) => Sym::Num(0. - number),
+                (IUnaryOperation::Neg, Sym::Unary(IUnaryOperation::Neg, expr)) => *expr,
+                (IUnaryOperation::Not, Sym::Bool(value)) => Sym::Bool(!value),
+                (op, expr) => Sym::Unary(*op, Box::new(expr)),
+            },
+            Sym::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.simplify_once(), rhs.simplify_once());
+                match op {
+                    IBinaryOperation::Add | IBinaryOperation::Sub => {
+                        let sign = if *op == IBinaryOperation::Add {
+                            1.
+                        } else {
+                            -1.
+                        };
+                        let (lc, lrest) = lhs.split();
+                        let (rc, rrest) = rhs.split();
+                        if lrest == rrest
+                            && let Some(sym) = coefficient(lc + sign * rc, lrest)
+                        {
+                            return sym;
+                        }
+                        match (lhs, rhs) {
+                            (lhs, Sym::Num(0.)) => lhs,
+                            (Sym::Num(0.), rhs) => {
+                                if *op == IBinaryOperation::Add {
+                                    rhs
+                                } else {
+                                    neg(rhs)
+                                }
+                            }
+                            (lhs, Sym::Unary(IUnaryOperation::Neg, rhs)) => binary(
+                                lhs,
+                                if *op == IBinaryOperation::Add {
+                                    IBinaryOperation::Sub
+                                } else {
+                                    IBinaryOperation::Add
+                                },
+                                *rhs,
+                            ),
+                            (lhs, rhs) => binary(lhs, *op, rhs),
+                        }
+                    }
+                    IBinaryOperation::Mul => {
+                        // Move divisions outwards: `a * (b / c)` and `a / b * c` become `a * b / c`
+                        match (lhs, rhs) {
+                            (lhs, Sym::Binary(num, IBinaryOperation::Div, den))
+                            | (Sym::Binary(num, IBinaryOperation::Div, den), lhs) => {
+                                binary(product(lhs, *num), IBinaryOperation::Div, *den)
+                            }
+                            (lhs, rhs) => {
+                                let (lc, lrest) = lhs.split();
+                                let (rc, rrest) = rhs.split();
+                                let rest = match (lrest, rrest) {
+                                    (Some(lhs), Some(rhs)) => Some(product(lhs, rhs)),
+                                    (lhs, rhs) => lhs.or(rhs),
+                                };
+                                coefficient(lc * rc, rest)
+                                    .unwrap_or_else(|| binary(lhs, IBinaryOperation::Mul, rhs))
+                            }
+                        }
+                    }
+                    IBinaryOperation::Div => match (lhs, rhs) {
+                        (Sym::Num(lhs), Sym::Num(rhs)) if rhs != 0. && (lhs / rhs).is_finite() => {
+                            Sym::Num(lhs / rhs)
+                        }
+                        (Sym::Num(0.), _) => Sym::Num(0.),
+                        (lhs, Sym::Num(1.)) => lhs,
+                        (lhs, rhs) if lhs.split().0 < 0. => {
+                            neg(binary(neg(lhs), IBinaryOperation::Div, rhs))
+                        }
+                        (lhs, rhs) => binary(lhs, IBinaryOperation::Div, rhs),
+                    },
+                    op => binary(lhs, *op, rhs),
+                }
+            }
+            Sym::Ternary(cnd, lhs, rhs) => {
+                match (
+                    cnd.simplify_once(),
+                    lhs.simplify_once(),
+                    rhs.simplify_once(),
+                ) {
+                    (Sym::Bool(cnd), lhs, rhs) => {
+                        if cnd {
+                            lhs
+                        } else {
+                            rhs
+                        }
+                    }
+                    (_, lhs, rhs) if lhs == rhs => lhs,
+                    (cnd, lhs, rhs) => Sym::Ternary(Box::new(cnd), Box::new(lhs), Box::new(rhs)),
+                }
+            }
+            Sym::Call(name, args) => {
+                Sym::Call(name.clone(), args.iter().map(Sym::simplify_once).collect())
+            }
+        }
+    }
+
+    /// Splits a product into its constant coefficient and the remaining factors.
+    fn split(&self) -> (f64, Option<Sym>) {
+        match self {
+            Sym::Num(number) => (*number, None),
+            Sym::Unary(IUnaryOperation::Neg, expr) => {
+                let (coef, rest) = expr.split();
+                (-coef, rest)
+            }
+            Sym::Binary(lhs, IBinaryOperation::Mul, rhs) => {
+                let (lc, lrest) = lhs.split();
+                let (rc, rrest) = rhs.split();
+                let rest = match (lrest, rrest) {
+                    (Some(lhs), Some(rhs)) => Some(product(lhs, rhs)),
+                    (lhs, rhs) => lhs.or(rhs),
+                };
+                (lc * rc, rest)
+            }
+            _ => (1., Some(self.clone())),
+        }
+    }
+
+    /// Binding strength when printed: operands weaker than their position
+    /// requires are parenthesized.
+    fn level(&self) -> u8 {
+        match self {
+            // The parser lets unary operators and ternaries swallow the rest of the expression
+            Sym::Unary(..) | Sym::Ternary(..) => 0,
+            Sym::Num(number) if number.is_sign_negative() => 0,
+            Sym::Binary(_, IBinaryOperation::Mul | IBinaryOperation::Div, _) => 2,
+            Sym::Binary(..) => 1,
+            Sym::Num(_) | Sym::Bool(_) | Sym::Var(_) | Sym::Call(..) => 3,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, level: u8) -> fmt::Result {
+        if self.level() < level {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+
+    fn fmt_branch(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sym::Ternary(..) => write!(f, "({self})"),
+            _ => write!(f, "{self}"),
+        }
+    }
+}
+
+fn neg(expr: Sym) -> Sym {
+    Sym::Unary(IUnaryOperation::Neg, Box::new(expr))
+}
+
+fn binary(lhs: Sym, op: IBinaryOperation, rhs: Sym) -> Sym {
+    Sym::Binary(Box::new(lhs), op, Box::new(rhs))
+}
+
+/// Multiplies keeping the chain left-nested, so it prints without parentheses.
+fn product(lhs: Sym, rhs: Sym) -> Sym {
+    match rhs {
+        Sym::Binary(inner, IBinaryOperation::Mul, factor) => {
+            binary(product(lhs, *inner), IBinaryOperation::Mul, *factor)
+        }
+        rhs => binary(lhs, IBinaryOperation::Mul, rhs),
+    }
+}
+
+/// Builds `coef * rest`, or `None` if the coefficient cannot be folded.
+fn coefficient(coef: f64, rest: Option<Sym>) -> Option<Sym> {
+    if !coef.is_finite() {
+        return None;
+    }
+    Some(match rest {
+        None => Sym::Num(coef),
+        Some(_) if coef == 0. => Sym::Num(0.),
+        Some(rest) if coef == 1. => rest,
+        Some(rest) if coef < 0. => neg(coefficient(-coef, Some(rest))?),
+        Some(rest) => product(Sym::Num(coef), rest),
+    })
+}
+
+impl fmt::Display for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sym::Num(number) => write!(f, "{number}"),
+            Sym::Bool(value) => write!(f, "{value}"),
+            Sym::Var(name) => write!(f, "{name}"),
+            Sym::Unary(op, expr) => {
+                let op = match op {
+                    IUnaryOperation::Neg => "-",
+                    IUnaryOperation::Pos => "+",
+                    IUnaryOperation::Not => "!",
+                };
+                write!(f, "{op}")?;
+                expr.fmt_operand(f, 3)
+            }
+            Sym::Binary(lhs, op, rhs) => {
+                let (symbol, level) = match op {
+                    IBinaryOperation::Add => ("+", 1),
+                    IBinaryOperation::Sub => ("-", 1),
+                    IBinaryOperation::Mul => ("*", 2),
+                    IBinaryOperation::Div => ("/", 2),
+                    IBinaryOperation::Lt => ("<", 1),
+                    IBinaryOperation::Le => ("<=", 1),
+                    IBinaryOperation::Eq => ("==", 1),
+                    IBinaryOperation::Ne => ("!=", 1),
+                    IBinaryOperation::Ge => (">=", 1),
+                    IBinaryOperation::Gt => (">", 1),
+                    IBinaryOperation::Custom(_) => unreachable!("operators are calls"),
+                };
+                // Operators of equal precedence group to the left
+                lhs.fmt_operand(f, level)?;
+                write!(f, " {symbol} ")?;
+                rhs.fmt_operand(f, level + 1)
+            }
+            // Parenthesized, since the precedence of the operator is unknown here
+            Sym::Call(name, args) if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') => {
+                write!(f, "({} {name} {})", args[0], args[1])
+            }
+            Sym::Call(name, args) => {
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            Sym::Ternary(cnd, lhs, rhs) => {
+                cnd.fmt_operand(f, 4)?;
+                write!(f, " ? ")?;
+                lhs.fmt_branch(f)?;
+                write!(f, " : ")?;
+                rhs.fmt_branch(f)
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::rc::Rc;
 use std::fmt;
 
+use super::parser::Program;
 use super::types::Span;
 
 pub type Que<'a> = Token<'a, IQue>;
@@ -15,10 +16,28 @@ pub type Lpar<'a> = Token<'a, ILpar>;
 
 pub type Rpar<'a> = Token<'a, IRpar>;
 
+pub type Lbrk<'a> = Token<'a, ILbrk>;
+
+pub type Rbrk<'a> = Token<'a, IRbrk>;
+
+pub type Lbrc<'a> = Token<'a, ILbrc>;
+
+pub type Rbrc<'a> = Token<'a, IRbrc>;
+
 pub type Int<'a> = Token<'a, IInt>;
 
 pub type Float<'a> = Token<'a, IFloat>;
 
+pub type Imaginary<'a> = Token<'a, IImaginary>;
+
+pub type Str<'a> = Token<'a, IStr>;
+
+pub type Comment<'a> = Token<'a, IComment>;
+
+pub type UnitExpr<'a> = Token<'a, IUnitExpr<'a>>;
+
+pub type Bool<'a> = Token<'a, IBool>;
+
 pub type Ident<'a> = Token<'a, IIdent>;
 
 pub type FuncCall<'a> = Token<'a, IFuncCall<'a>>;
@@ -37,6 +56,20 @@ pub type FuncAssign<'a> = Token<'a, IFuncAssign<'a>>;
 
 pub type FuncAssignArgs<'a> = Token<'a, IFuncAssignArgs<'a>>;
 
+pub type Clause<'a> = Token<'a, IClause<'a>>;
+
+pub type Piecewise<'a> = Token<'a, IPiecewise<'a>>;
+
+pub type Block<'a> = Token<'a, IBlock<'a>>;
+
+pub type If<'a> = Token<'a, IIf<'a>>;
+
+pub type While<'a> = Token<'a, IWhile<'a>>;
+
+pub type For<'a> = Token<'a, IFor<'a>>;
+
+pub type Import<'a> = Token<'a, IImport<'a>>;
+
 #[derive(Debug, Clone)]
 pub struct Token<'a, T> {
     pub pos: Span<'a>,
@@ -67,7 +100,9 @@ macro_rules! define_empty_struct {
     };
 }
 
-define_empty_struct!(IQue, ICol, IEol, IEql, ILpar, IRpar);
+define_empty_struct!(
+    IQue, ICol, IEol, IEql, ILpar, IRpar, ILbrk, IRbrk, ILbrc, IRbrc
+);
 
 #[derive(Debug, Clone)]
 pub struct IInt(pub i64);
@@ -75,12 +110,40 @@ pub struct IInt(pub i64);
 #[derive(Debug, Clone)]
 pub struct IFloat(pub f64);
 
+#[derive(Debug, Clone)]
+pub struct IImaginary(pub f64);
+
 #[derive(Debug, Clone)]
 pub enum Number<'a> {
     Int(Int<'a>),
     Float(Float<'a>),
+    Imaginary(Imaginary<'a>),
 }
 
+impl<'a> Number<'a> {
+    pub fn pos(&self) -> Span<'a> {
+        match self {
+            Number::Int(token) => token.pos,
+            Number::Float(token) => token.pos,
+            Number::Imaginary(token) => token.pos,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IStr(pub String);
+
+/// Text of a `# line` or `/* block */` comment, without the delimiters.
+#[derive(Debug, Clone)]
+pub struct IComment(pub String);
+
+/// Unit names with their exponents, e.g. `m/s^2` is `[(m, 1), (s, -2)]`.
+#[derive(Debug, Clone)]
+pub struct IUnitExpr<'a>(pub Vec<(Ident<'a>, i8)>);
+
+#[derive(Debug, Clone)]
+pub struct IBool(pub bool);
+
 #[derive(Debug, Clone)]
 pub struct IIdent(pub String);
 
@@ -93,14 +156,14 @@ pub struct IFuncCall<'a> {
     pub args: FuncCallArgs<'a>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum IUnaryOperation {
     Neg,
     Pos,
     Not,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum IBinaryOperation {
     Add,
     Sub,
@@ -113,6 +176,40 @@ pub enum IBinaryOperation {
     Ne,
     Ge,
     Gt,
+
+    /// Operator defined with `infixl` or `infixr`, named by the text of its
+    /// token.
+    Custom(Fixity),
+}
+
+impl IBinaryOperation {
+    /// Built-in operators other than `*` and `/` share a precedence, and
+    /// group from left to right.
+    pub fn fixity(&self) -> Fixity {
+        let precedence = match self {
+            IBinaryOperation::Mul | IBinaryOperation::Div => 7,
+            IBinaryOperation::Custom(fixity) => return *fixity,
+            _ => 6,
+        };
+        Fixity {
+            precedence,
+            associativity: Associativity::Left,
+        }
+    }
+}
+
+/// How tightly an operator binds, from 0 to 9, and how operators of equal
+/// precedence group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fixity {
+    pub precedence: u8,
+    pub associativity: Associativity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
 }
 
 #[derive(Debug, Clone)]
@@ -120,9 +217,16 @@ pub enum IExpression<'a> {
     Call(FuncCall<'a>),
     Ident(Ident<'a>),
     Number(Number<'a>),
+    Quantity(Number<'a>, UnitExpr<'a>),
+    Str(Str<'a>),
+    Bool(Bool<'a>),
     Unary(Expression<'a>, UnaryOperation<'a>),
     Binary(Expression<'a>, BinaryOperation<'a>, Expression<'a>),
     Ternary(Expression<'a>, Expression<'a>, Expression<'a>),
+    Interval(Expression<'a>, Expression<'a>),
+    List(Vec<Expression<'a>>),
+    /// `let a = 1, b = a + 1 in a * b`
+    Let(Vec<(Ident<'a>, Expression<'a>)>, Expression<'a>),
 }
 
 #[derive(Debug, Clone)]
@@ -135,6 +239,8 @@ pub enum VarAssignExpr<'a> {
 pub struct IVarAssign<'a> {
     pub ident: Ident<'a>,
     pub expr: VarAssignExpr<'a>,
+    /// Declared with `const`, so that it cannot be reassigned.
+    pub constant: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -142,7 +248,100 @@ pub struct IFuncAssign<'a> {
     pub ident: Ident<'a>,
     pub args: FuncAssignArgs<'a>,
     pub expr: Expression<'a>,
+    /// Declared with `memo`, so that results are cached by arguments.
+    pub memo: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct IFuncAssignArgs<'a>(pub Vec<Ident<'a>>);
+
+/// A parameter of a function clause.
+#[derive(Debug, Clone)]
+pub enum Param<'a> {
+    Ident(Ident<'a>),
+    /// Only matches arguments equal to the literal, like the `0` in `fact(0) = 1`.
+    Literal(Expression<'a>),
+}
+
+/// One clause of a piecewise function, e.g. `f(x) | x < 0 = -x | otherwise = x`.
+#[derive(Debug, Clone)]
+pub struct IClause<'a> {
+    pub params: Vec<Param<'a>>,
+    /// Bodies with the guards selecting them, tried in order. An unguarded
+    /// body or `otherwise` has no guard.
+    pub guards: Vec<(Option<Expression<'a>>, Expression<'a>)>,
+}
+
+impl IClause<'_> {
+    /// Whether the clause applies to any arguments.
+    pub fn is_catch_all(&self) -> bool {
+        self.params
+            .iter()
+            .all(|param| matches!(param, Param::Ident(_)))
+            && self.guards.iter().any(|(guard, _)| guard.is_none())
+    }
+}
+
+/// Function defined by clauses tried in order, e.g.
+/// `fact(0) = 1; fact(n) = n * fact(n - 1)`.
+#[derive(Debug, Clone)]
+pub struct IPiecewise<'a> {
+    pub ident: Ident<'a>,
+    pub clauses: Vec<Clause<'a>>,
+    /// Declared with `memo`, so that results are cached by arguments.
+    pub memo: bool,
+}
+
+impl IPiecewise<'_> {
+    pub fn argc(&self) -> usize {
+        self.clauses[0].data.params.len()
+    }
+
+    /// Whether every call matches some clause.
+    pub fn is_exhaustive(&self) -> bool {
+        self.clauses.iter().any(|clause| clause.data.is_catch_all())
+    }
+}
+
+/// Statements between braces.
+#[derive(Debug, Clone)]
+pub struct IBlock<'a>(pub Vec<Program<'a>>);
+
+/// `if cond { ... } else { ... }`; an `else if` is an `else` block holding
+/// the nested `if`.
+#[derive(Debug, Clone)]
+pub struct IIf<'a> {
+    pub cond: Expression<'a>,
+    pub then: Block<'a>,
+    pub otherwise: Option<Block<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IWhile<'a> {
+    pub cond: Expression<'a>,
+    pub body: Block<'a>,
+}
+
+/// What a `for` loop iterates over.
+#[derive(Debug, Clone)]
+pub enum ForIter<'a> {
+    /// `a..b`, the numbers from `a` up to but excluding `b`.
+    Range(Expression<'a>, Expression<'a>),
+    /// The items of a list.
+    List(Expression<'a>),
+}
+
+#[derive(Debug, Clone)]
+pub struct IFor<'a> {
+    pub var: Ident<'a>,
+    pub iter: ForIter<'a>,
+    pub body: Block<'a>,
+}
+
+/// `import "geo.ne"` or `import "lib/geometry.ne" as geo`.
+#[derive(Debug, Clone)]
+pub struct IImport<'a> {
+    pub path: Str<'a>,
+    /// Namespace of the definitions; the file stem if not given.
+    pub alias: Option<Ident<'a>>,
+}
@@ -15,12 +15,12 @@ pub trait SpanExt<'a> {
 
 impl<'a> SpanExt<'a> for Span<'a> {
     fn diff(&self, other: &Span) -> Span<'a> {
-        let offset: usize = self.offset(&other);
+        let offset: usize = self.offset(other);
         self.take(offset)
     }
 
     fn including_diff(&self, other: &Span) -> Span<'a> {
-        let len = self.offset(&other) + other.len();
+        let len = self.offset(other) + other.len();
         self.take(len)
     }
 }
@@ -43,7 +43,7 @@ impl<'a> Error<'a> {
 impl<'a> nom::error::ParseError<Span<'a>> for Error<'a> {
     fn from_error_kind(input: Span<'a>, kind: nom::error::ErrorKind) -> Self {
         Error::new(input, unsafe {
-            transmute::<_, &'static str>(kind.description())
+            transmute::<&str, &'static str>(kind.description())
         })
     }
 
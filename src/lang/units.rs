@@ -0,0 +1,182 @@
+use std::fmt;
+use std::ops::{Div, Mul};
+use std::rc::Rc;
+
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// Exponents of the SI base units, in the order of [`BASE_UNITS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Dimension(pub [i8; 7]);
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension([0, 0, 0, 0, 0, 0, 0]);
+    pub const LENGTH: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+    pub const MASS: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+    pub const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+    pub const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+    pub const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+    pub const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+    pub const LUMINOSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+
+    pub fn is_none(self) -> bool {
+        self == Dimension::NONE
+    }
+
+    pub fn pow(self, exp: i8) -> Dimension {
+        Dimension(self.0.map(|dim| dim * exp))
+    }
+
+    /// Halves every exponent, if they are all even.
+    pub fn sqrt(self) -> Option<Dimension> {
+        self.0
+            .iter()
+            .all(|dim| dim % 2 == 0)
+            .then(|| Dimension(self.0.map(|dim| dim / 2)))
+    }
+}
+
+impl Mul for Dimension {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Dimension(std::array::from_fn(|idx| self.0[idx] + rhs.0[idx]))
+    }
+}
+
+impl Div for Dimension {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Dimension(std::array::from_fn(|idx| self.0[idx] - rhs.0[idx]))
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join(dims: impl Iterator<Item = (&'static str, i8)>) -> String {
+            dims.map(|(name, exp)| match exp {
+                1 => name.to_string(),
+                exp => format!("{name}^{exp}"),
+            })
+            .collect::<Vec<_>>()
+            .join("*")
+        }
+
+        let dims = || BASE_UNITS.into_iter().zip(self.0);
+        let num = join(dims().filter(|(_, exp)| *exp > 0));
+        let den = join(dims().filter(|(_, exp)| *exp < 0).map(|(n, e)| (n, -e)));
+        match (num.is_empty(), den.is_empty()) {
+            (true, true) => write!(f, "1"),
+            (false, true) => write!(f, "{num}"),
+            (true, false) => write!(f, "1/{den}"),
+            (false, false) => write!(f, "{num}/{den}"),
+        }
+    }
+}
+
+/// A named unit: `scale` SI base units of the given dimension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub scale: f64,
+    pub dim: Dimension,
+}
+
+impl Unit {
+    pub fn new(scale: f64, dim: Dimension) -> Self {
+        Self { scale, dim }
+    }
+
+    pub fn pow(self, exp: i8) -> Unit {
+        Unit::new(self.scale.powi(exp as i32), self.dim.pow(exp))
+    }
+}
+
+impl Mul for Unit {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Unit::new(self.scale * rhs.scale, self.dim * rhs.dim)
+    }
+}
+
+impl Div for Unit {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Unit::new(self.scale / rhs.scale, self.dim / rhs.dim)
+    }
+}
+
+pub fn default_units() -> Vec<(&'static str, Unit)> {
+    let m = Unit::new(1., Dimension::LENGTH);
+    let kg = Unit::new(1., Dimension::MASS);
+    let s = Unit::new(1., Dimension::TIME);
+    let newton = kg * m / s.pow(2);
+    let joule = newton * m;
+    vec![
+        ("m", m),
+        ("km", Unit::new(1e3, Dimension::LENGTH)),
+        ("cm", Unit::new(1e-2, Dimension::LENGTH)),
+        ("mm", Unit::new(1e-3, Dimension::LENGTH)),
+        ("kg", kg),
+        ("g", Unit::new(1e-3, Dimension::MASS)),
+        ("t", Unit::new(1e3, Dimension::MASS)),
+        ("s", s),
+        ("ms", Unit::new(1e-3, Dimension::TIME)),
+        ("min", Unit::new(60., Dimension::TIME)),
+        ("h", Unit::new(3600., Dimension::TIME)),
+        ("A", Unit::new(1., Dimension::CURRENT)),
+        ("K", Unit::new(1., Dimension::TEMPERATURE)),
+        ("mol", Unit::new(1., Dimension::AMOUNT)),
+        ("cd", Unit::new(1., Dimension::LUMINOSITY)),
+        ("L", Unit::new(1e-3, Dimension::LENGTH.pow(3))),
+        ("Hz", s.pow(-1)),
+        ("N", newton),
+        ("Pa", newton / m.pow(2)),
+        ("J", joule),
+        ("W", joule / s),
+    ]
+}
+
+/// A dimensioned value stored in SI base units. `unit` remembers the unit
+/// the value was written in (or converted to) for display purposes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub dim: Dimension,
+    pub unit: Option<(Rc<str>, f64)>,
+}
+
+impl Quantity {
+    pub fn new(value: f64, dim: Dimension) -> Self {
+        Self {
+            value,
+            dim,
+            unit: None,
+        }
+    }
+
+    pub fn with_unit(value: f64, unit: Unit, name: &str) -> Self {
+        Self {
+            value: value * unit.scale,
+            dim: unit.dim,
+            unit: Some((name.into(), unit.scale)),
+        }
+    }
+}
+
+impl Quantity {
+    /// Writes the quantity with its magnitude written by `number`.
+    pub fn format_with(&self, number: impl Fn(f64) -> String) -> String {
+        match &self.unit {
+            Some((name, scale)) => format!("{} {name}", number(self.value / scale)),
+            None => format!("{} {}", number(self.value), self.dim),
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_with(|value| value.to_string()))
+    }
+}
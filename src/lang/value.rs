@@ -0,0 +1,489 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use super::complex::Complex;
+use super::decimal::{Decimal, Rounding};
+use super::dual::Dual;
+use super::interval::Interval;
+use super::tokens::IBinaryOperation;
+use super::units::{Dimension, Quantity};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    /// Base-10 number produced in decimal mode.
+    Decimal(Decimal),
+    Bool(bool),
+    Str(Rc<str>),
+    Complex(Complex),
+    Quantity(Quantity),
+    Interval(Interval),
+    Dual(Dual),
+    List(Rc<[Value]>),
+    /// Reference to a function by name, passed to builtins such as `diff`.
+    Func(Rc<str>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticError {
+    InvalidOperands,
+    DivisionByZero,
+    Overflow,
+    DimensionMismatch(Dimension, Dimension),
+    /// The order of overlapping intervals is not known.
+    Indeterminate,
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Decimal(_) => "decimal",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "string",
+            Value::Complex(_) => "complex",
+            Value::Quantity(_) => "quantity",
+            Value::Interval(_) => "interval",
+            Value::Dual(_) => "dual",
+            Value::List(_) => "list",
+            Value::Func(_) => "function",
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Value::Number(_)
+                | Value::Decimal(_)
+                | Value::Complex(_)
+                | Value::Quantity(_)
+                | Value::Interval(_)
+                | Value::Dual(_)
+        )
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0.,
+            Value::Decimal(decimal) => !decimal.is_zero(),
+            Value::Bool(bool) => *bool,
+            Value::Str(string) => !string.is_empty(),
+            Value::Complex(complex) => !complex.is_zero(),
+            Value::Quantity(quantity) => quantity.value != 0.,
+            Value::Interval(interval) => *interval != Interval::point(0.),
+            Value::Dual(dual) => dual.re != 0.,
+            Value::List(list) => !list.is_empty(),
+            Value::Func(_) => true,
+        }
+    }
+
+    pub fn as_number(&self) -> Result<f64, String> {
+        match self {
+            Value::Number(number) => Ok(*number),
+            Value::Decimal(decimal) => Ok(decimal.to_f64()),
+            Value::Bool(bool) => Ok(if *bool { 1. } else { 0. }),
+            other => Err(format!("Expected number, got {}", other.type_name())),
+        }
+    }
+
+    pub fn as_complex(&self) -> Result<Complex, String> {
+        match self {
+            Value::Complex(complex) => Ok(*complex),
+            other => other.as_number().map(Complex::from),
+        }
+    }
+
+    /// Demotes complex numbers without an imaginary part back to real numbers.
+    pub fn complex(complex: Complex) -> Value {
+        if complex.im == 0. {
+            Value::Number(complex.re)
+        } else {
+            Value::Complex(complex)
+        }
+    }
+
+    pub fn as_quantity(&self) -> Result<Quantity, String> {
+        match self {
+            Value::Quantity(quantity) => Ok(quantity.clone()),
+            other => other
+                .as_number()
+                .map(|number| Quantity::new(number, Dimension::NONE)),
+        }
+    }
+
+    /// Demotes dimensionless quantities back to real numbers.
+    pub fn quantity(quantity: Quantity) -> Value {
+        if quantity.dim.is_none() {
+            Value::Number(quantity.value)
+        } else {
+            Value::Quantity(quantity)
+        }
+    }
+
+    pub fn as_interval(&self) -> Result<Interval, String> {
+        match self {
+            Value::Interval(interval) => Ok(*interval),
+            other => other.as_number().map(Interval::point),
+        }
+    }
+
+    pub fn as_dual(&self) -> Result<Dual, String> {
+        match self {
+            Value::Dual(dual) => Ok(*dual),
+            other => other.as_number().map(Dual::from),
+        }
+    }
+
+    pub fn neg(&self) -> Result<Value, ArithmeticError> {
+        match self {
+            Value::Number(number) => Ok(Value::Number(-number)),
+            Value::Decimal(decimal) => Ok(Value::Decimal(-*decimal)),
+            Value::Complex(complex) => Ok(Value::Complex(-*complex)),
+            Value::Quantity(quantity) => Ok(Value::Quantity(Quantity {
+                value: -quantity.value,
+                ..quantity.clone()
+            })),
+            Value::Interval(interval) => Ok(Value::Interval(-*interval)),
+            Value::Dual(dual) => Ok(Value::Dual(-*dual)),
+            _ => Err(ArithmeticError::InvalidOperands),
+        }
+        .and_then(Value::finite)
+    }
+
+    /// Applies an arithmetic operation, promoting operands to the wider numeric type.
+    pub fn arithmetic(&self, op: &IBinaryOperation, rhs: &Value) -> Result<Value, ArithmeticError> {
+        match (self, rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => Ok(Value::Number(match op {
+                IBinaryOperation::Add => lhs + rhs,
+                IBinaryOperation::Sub => lhs - rhs,
+                IBinaryOperation::Mul => lhs * rhs,
+                IBinaryOperation::Div if *rhs == 0. => return Err(ArithmeticError::DivisionByZero),
+                IBinaryOperation::Div => lhs / rhs,
+                _ => return Err(ArithmeticError::InvalidOperands),
+            })),
+            (Value::Decimal(lhs), Value::Decimal(rhs)) => match op {
+                IBinaryOperation::Add => lhs.checked_add(*rhs),
+                IBinaryOperation::Sub => lhs.checked_sub(*rhs),
+                IBinaryOperation::Mul => lhs.checked_mul(*rhs, Rounding::default()),
+                // Exact division needs the scale of the decimal mode, see `Context`
+                IBinaryOperation::Div => {
+                    return Value::Number(lhs.to_f64())
+                        .arithmetic(op, &Value::Number(rhs.to_f64()));
+                }
+                _ => return Err(ArithmeticError::InvalidOperands),
+            }
+            .map(Value::Decimal)
+            .ok_or(ArithmeticError::Overflow),
+            (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+                undecimal(self).arithmetic(op, &undecimal(rhs))
+            }
+            (Value::Str(lhs), Value::Str(rhs)) => match op {
+                IBinaryOperation::Add => Ok(format!("{lhs}{rhs}").into()),
+                _ => Err(ArithmeticError::InvalidOperands),
+            },
+            (Value::Interval(_), Value::Number(_) | Value::Interval(_))
+            | (Value::Number(_), Value::Interval(_)) => {
+                let (lhs, rhs) = (self.as_interval().unwrap(), rhs.as_interval().unwrap());
+                Ok(Value::Interval(match op {
+                    IBinaryOperation::Add => lhs + rhs,
+                    IBinaryOperation::Sub => lhs - rhs,
+                    IBinaryOperation::Mul => lhs * rhs,
+                    IBinaryOperation::Div => lhs
+                        .checked_div(rhs)
+                        .ok_or(ArithmeticError::DivisionByZero)?,
+                    _ => return Err(ArithmeticError::InvalidOperands),
+                }))
+            }
+            (Value::Dual(_), Value::Number(_) | Value::Dual(_))
+            | (Value::Number(_), Value::Dual(_)) => {
+                let (lhs, rhs) = (self.as_dual().unwrap(), rhs.as_dual().unwrap());
+                Ok(Value::Dual(match op {
+                    IBinaryOperation::Add => lhs + rhs,
+                    IBinaryOperation::Sub => lhs - rhs,
+                    IBinaryOperation::Mul => lhs * rhs,
+                    IBinaryOperation::Div if rhs.re == 0. => {
+                        return Err(ArithmeticError::DivisionByZero);
+                    }
+                    IBinaryOperation::Div => lhs / rhs,
+                    _ => return Err(ArithmeticError::InvalidOperands),
+                }))
+            }
+            (Value::Quantity(_), Value::Number(_) | Value::Quantity(_))
+            | (Value::Number(_), Value::Quantity(_)) => {
+                quantity_arithmetic(op, self.as_quantity().unwrap(), rhs.as_quantity().unwrap())
+            }
+            (Value::Number(_) | Value::Complex(_), Value::Number(_) | Value::Complex(_)) => {
+                let (lhs, rhs) = (self.as_complex().unwrap(), rhs.as_complex().unwrap());
+                Ok(Value::complex(match op {
+                    IBinaryOperation::Add => lhs + rhs,
+                    IBinaryOperation::Sub => lhs - rhs,
+                    IBinaryOperation::Mul => lhs * rhs,
+                    IBinaryOperation::Div if rhs.is_zero() => {
+                        return Err(ArithmeticError::DivisionByZero);
+                    }
+                    IBinaryOperation::Div => lhs / rhs,
+                    _ => return Err(ArithmeticError::InvalidOperands),
+                }))
+            }
+            _ => Err(ArithmeticError::InvalidOperands),
+        }
+        .and_then(Value::finite)
+    }
+
+    /// Compares values of compatible types.
+    pub fn ordering(&self, rhs: &Value) -> Result<Ordering, ArithmeticError> {
+        match (self, rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => {
+                lhs.partial_cmp(rhs).ok_or(ArithmeticError::InvalidOperands)
+            }
+            (Value::Str(lhs), Value::Str(rhs)) => Ok(lhs.cmp(rhs)),
+            (Value::Decimal(lhs), Value::Decimal(rhs)) => Ok(lhs.cmp(rhs)),
+            (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+                undecimal(self).ordering(&undecimal(rhs))
+            }
+            (Value::Quantity(_), Value::Number(_) | Value::Quantity(_))
+            | (Value::Number(_), Value::Quantity(_)) => {
+                let (lhs, rhs) = (self.as_quantity().unwrap(), rhs.as_quantity().unwrap());
+                if lhs.dim != rhs.dim {
+                    return Err(ArithmeticError::DimensionMismatch(lhs.dim, rhs.dim));
+                }
+                lhs.value
+                    .partial_cmp(&rhs.value)
+                    .ok_or(ArithmeticError::InvalidOperands)
+            }
+            (Value::Interval(_), Value::Number(_) | Value::Interval(_))
+            | (Value::Number(_), Value::Interval(_)) => {
+                let (lhs, rhs) = (self.as_interval().unwrap(), rhs.as_interval().unwrap());
+                if lhs.hi < rhs.lo {
+                    Ok(Ordering::Less)
+                } else if lhs.lo > rhs.hi {
+                    Ok(Ordering::Greater)
+                } else if lhs.is_point() && lhs == rhs {
+                    Ok(Ordering::Equal)
+                } else {
+                    Err(ArithmeticError::Indeterminate)
+                }
+            }
+            // Branches are chosen by the value, the derivative just follows along
+            (Value::Dual(_), Value::Number(_) | Value::Dual(_))
+            | (Value::Number(_), Value::Dual(_)) => {
+                let (lhs, rhs) = (self.as_dual().unwrap(), rhs.as_dual().unwrap());
+                lhs.re
+                    .partial_cmp(&rhs.re)
+                    .ok_or(ArithmeticError::InvalidOperands)
+            }
+            _ => Err(ArithmeticError::InvalidOperands),
+        }
+    }
+
+    /// Checks equality of compatible types; `InvalidOperands` if the types
+    /// are unrelated. Overlapping intervals are equal only if they are the
+    /// same point, and otherwise their equality is not known either.
+    pub fn equals(&self, rhs: &Value) -> Result<bool, ArithmeticError> {
+        match (self, rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => Ok(lhs == rhs),
+            (Value::Bool(lhs), Value::Bool(rhs)) => Ok(lhs == rhs),
+            (Value::Str(lhs), Value::Str(rhs)) => Ok(lhs == rhs),
+            (Value::Decimal(lhs), Value::Decimal(rhs)) => Ok(lhs == rhs),
+            (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+                undecimal(self).equals(&undecimal(rhs))
+            }
+            (Value::Number(_) | Value::Complex(_), Value::Number(_) | Value::Complex(_)) => {
+                Ok(self.as_complex().unwrap() == rhs.as_complex().unwrap())
+            }
+            (Value::Quantity(lhs), Value::Quantity(rhs)) if lhs.dim == rhs.dim => {
+                Ok(lhs.value == rhs.value)
+            }
+            (Value::Interval(_), Value::Number(_) | Value::Interval(_))
+            | (Value::Number(_), Value::Interval(_)) => self.ordering(rhs).map(Ordering::is_eq),
+            (Value::Dual(_), Value::Number(_) | Value::Dual(_))
+            | (Value::Number(_), Value::Dual(_)) => {
+                Ok(self.as_dual().unwrap().re == rhs.as_dual().unwrap().re)
+            }
+            (Value::List(lhs), Value::List(rhs)) => {
+                if lhs.len() != rhs.len() {
+                    return Ok(false);
+                }
+                // Lists differ if any items do, even if others cannot be told apart
+                let mut equal = Ok(true);
+                for (l, r) in lhs.iter().zip(rhs.iter()) {
+                    match l.equals(r) {
+                        Ok(true) => {}
+                        Err(ArithmeticError::Indeterminate) => {
+                            equal = Err(ArithmeticError::Indeterminate)
+                        }
+                        Ok(false) | Err(_) => return Ok(false),
+                    }
+                }
+                equal
+            }
+            (Value::Func(lhs), Value::Func(rhs)) => Ok(lhs == rhs),
+            _ => Err(ArithmeticError::InvalidOperands),
+        }
+    }
+
+    fn finite(self) -> Result<Value, ArithmeticError> {
+        let finite = match &self {
+            Value::Number(number) => number.is_finite(),
+            Value::Complex(complex) => complex.is_finite(),
+            Value::Quantity(quantity) => quantity.value.is_finite(),
+            Value::Interval(interval) => interval.is_finite(),
+            Value::Dual(dual) => dual.is_finite(),
+            _ => true,
+        };
+        if finite {
+            Ok(self)
+        } else {
+            Err(ArithmeticError::Overflow)
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, String> {
+        match self {
+            Value::Str(string) => Ok(string),
+            other => Err(format!("Expected string, got {}", other.type_name())),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value.into())
+    }
+}
+
+impl PartialEq<f64> for Value {
+    fn eq(&self, other: &f64) -> bool {
+        match self {
+            Value::Number(number) => number == other,
+            Value::Decimal(decimal) => decimal.to_f64() == *other,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{number}"),
+            Value::Decimal(decimal) => write!(f, "{decimal}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Str(string) => write!(f, "{string}"),
+            Value::Complex(complex) => write!(f, "{complex}"),
+            Value::Quantity(quantity) => write!(f, "{quantity}"),
+            Value::Interval(interval) => write!(f, "{interval}"),
+            Value::Dual(dual) => write!(f, "{dual}"),
+            Value::List(list) => {
+                write!(f, "[")?;
+                for (idx, value) in list.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Func(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Falls back to binary floating point for mixed operands.
+fn undecimal(value: &Value) -> Value {
+    match value {
+        Value::Decimal(decimal) => Value::Number(decimal.to_f64()),
+        value => value.clone(),
+    }
+}
+
+fn quantity_arithmetic(
+    op: &IBinaryOperation,
+    lhs: Quantity,
+    rhs: Quantity,
+) -> Result<Value, ArithmeticError> {
+    let quantity = match op {
+        IBinaryOperation::Add | IBinaryOperation::Sub => {
+            if lhs.dim != rhs.dim {
+                return Err(ArithmeticError::DimensionMismatch(lhs.dim, rhs.dim));
+            }
+            Quantity {
+                value: match op {
+                    IBinaryOperation::Add => lhs.value + rhs.value,
+                    _ => lhs.value - rhs.value,
+                },
+                dim: lhs.dim,
+                unit: lhs.unit.or(rhs.unit),
+            }
+        }
+        IBinaryOperation::Mul => Quantity {
+            value: lhs.value * rhs.value,
+            dim: lhs.dim * rhs.dim,
+            // Scaling by a plain number keeps the unit the value was written in
+            unit: match (lhs.dim.is_none(), rhs.dim.is_none()) {
+                (true, _) => rhs.unit,
+                (_, true) => lhs.unit,
+                _ => None,
+            },
+        },
+        IBinaryOperation::Div if rhs.value == 0. => return Err(ArithmeticError::DivisionByZero),
+        IBinaryOperation::Div => Quantity {
+            value: lhs.value / rhs.value,
+            dim: lhs.dim / rhs.dim,
+            unit: if rhs.dim.is_none() { lhs.unit } else { None },
+        },
+        _ => return Err(ArithmeticError::InvalidOperands),
+    };
+    Ok(Value::quantity(quantity))
+}
+
+/// Substitutes `{}` placeholders in `template` with `args` in order, each
+/// written by `show`. `{{` and `}}` produce literal braces.
+pub fn format_template(
+    template: &str,
+    args: &[Value],
+    show: impl Fn(&Value) -> String,
+) -> Result<String, String> {
+    let mut result = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(ch);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                match args.next() {
+                    Some(arg) => result.push_str(&show(arg)),
+                    None => return Err("Not enough arguments for format string".into()),
+                }
+            }
+            ('{', _) | ('}', _) => return Err(format!("Unmatched '{ch}' in format string")),
+            _ => result.push(ch),
+        }
+    }
+    if args.next().is_some() {
+        return Err("Too many arguments for format string".into());
+    }
+    Ok(result)
+}
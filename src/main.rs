@@ -1,14 +1,14 @@
 use core::str;
 use nelang::lang::{
-    Context, Func, IExpression, IFloat, Number, Program, Span, Token, VarAssign, VarAssignExpr,
-    program,
+    Context, DecimalMode, EvaluateExpressionError, ExecuteError, Func, IExpression, IFloat,
+    MAX_SCALE, Number, NumberFormat, Program, Span, Token, VarAssign, VarAssignExpr, derive_call,
 };
-use nom::multi::many1;
-use nom::{Err, Offset, Parser};
+use nom::{Err, Offset};
 use std::borrow::Cow;
 use std::env::args;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{
     io::{Write, stdin, stdout},
@@ -60,9 +60,36 @@ fn handle_user_input<'a>(
         .map_err(|_| "Invalid input. Expected a number.".to_string())
 }
 
-fn repl_main() {
+#[derive(Default)]
+struct Options {
+    strict: bool,
+    complex: bool,
+    interval: bool,
+    decimal: Option<DecimalMode>,
+    seed: Option<u64>,
+    format: NumberFormat,
+    fuel: Option<u64>,
+}
+
+impl Options {
+    fn context<'a>(&self) -> Context<'a> {
+        let mut ctx = Context::new();
+        ctx.strict = self.strict;
+        ctx.complex = self.complex;
+        ctx.interval = self.interval;
+        ctx.decimal = self.decimal;
+        ctx.format = self.format;
+        ctx.fuel = self.fuel;
+        if let Some(seed) = self.seed {
+            ctx.seed(seed);
+        }
+        ctx
+    }
+}
+
+fn repl_main(options: Options) {
     let mut storage = Vec::<Rc<String>>::new();
-    let mut ctx = Context::new();
+    let mut ctx = options.context();
     loop {
         print!("> ");
         stdout().flush().unwrap();
@@ -74,6 +101,18 @@ fn repl_main() {
                     "Available commands:
 help - Print this help message.
 help functions - List all defined functions and their signatures.
+strict on|off - Enable or disable strict boolean typing.
+complex on|off - Enable or disable complex results for 'sqrt' and 'ln'.
+interval on|off - Enable or disable interval evaluation.
+decimal on|off - Enable or disable decimal fixed-point evaluation.
+decimal scale <n> - Set the number of decimal places in decimal mode.
+decimal rounding <mode> - Set the rounding in decimal mode: half-even, half-up or truncate.
+format <notation> - Set how numbers are shown: auto, fixed <n>, sig <n>, sci [n], eng [n], hex or bin.
+format separators on|off - Enable or disable thousands separators.
+fuel <n>|off - Limit the steps a statement may take, or remove the limit.
+memo - Show how many results of 'memo' functions are cached and how often they were used.
+memo clear - Drop the cached results of 'memo' functions.
+derive(f, x) - Define f' as the symbolic derivative of f with respect to x.
 clear - Clear the screen.
 exit - Exit the program.
 You can also enter expressions, variable declarations, or function definitions directly."
@@ -83,12 +122,103 @@ You can also enter expressions, variable declarations, or function definitions d
             "help functions" => {
                 ctx.funcs.iter().for_each(|(k, v)| match v {
                     Func::Builtin { argc, .. } => println!("{k}({argc})"),
+                    Func::Variadic { min_argc, .. } => println!("{k}({min_argc}+)"),
+                    Func::Intrinsic { argc, .. } if argc.start() == argc.end() => {
+                        println!("{k}({})", argc.start())
+                    }
+                    Func::Intrinsic { argc, .. } if *argc.end() == usize::MAX => {
+                        println!("{k}({}+)", argc.start())
+                    }
+                    Func::Intrinsic { argc, .. } => {
+                        println!("{k}({}-{})", argc.start(), argc.end())
+                    }
                     Func::Custom(token) => {
                         println!("{k}({}) builtin", token.data.args.data.0.len())
                     }
+                    Func::Piecewise(token) => println!("{k}({})", token.data.argc()),
+                    Func::Imported { func, .. } => {
+                        let argc = func.arity();
+                        println!("{k}({})", argc.start())
+                    }
                 });
                 continue;
             }
+            "strict on" | "strict off" => {
+                ctx.strict = string.trim() == "strict on";
+                continue;
+            }
+            "complex on" | "complex off" => {
+                ctx.complex = string.trim() == "complex on";
+                continue;
+            }
+            "interval on" | "interval off" => {
+                ctx.interval = string.trim() == "interval on";
+                continue;
+            }
+            "decimal on" => {
+                ctx.decimal.get_or_insert_default();
+                continue;
+            }
+            "decimal off" => {
+                ctx.decimal = None;
+                continue;
+            }
+            command if command.starts_with("decimal scale ") => {
+                match command["decimal scale ".len()..].trim().parse() {
+                    Ok(scale) if scale <= MAX_SCALE => {
+                        ctx.decimal.get_or_insert_default().scale = scale
+                    }
+                    _ => println!("Expected an integer between 0 and {MAX_SCALE}."),
+                }
+                continue;
+            }
+            command if command.starts_with("decimal rounding ") => {
+                match command["decimal rounding ".len()..].trim().parse() {
+                    Ok(rounding) => ctx.decimal.get_or_insert_default().rounding = rounding,
+                    Err(err) => println!("{err}."),
+                }
+                continue;
+            }
+            "memo" => {
+                let memo = ctx.memo.borrow();
+                println!(
+                    "Cached results: {}, hits: {}, misses: {}",
+                    memo.len(),
+                    memo.hits,
+                    memo.misses
+                );
+                continue;
+            }
+            "memo clear" => {
+                ctx.memo.get_mut().clear();
+                continue;
+            }
+            "fuel off" => {
+                ctx.fuel = None;
+                continue;
+            }
+            command if command.starts_with("fuel ") => {
+                match command["fuel ".len()..].trim().parse() {
+                    Ok(fuel) => ctx.fuel = Some(fuel),
+                    Err(_) => println!("Expected a non-negative integer."),
+                }
+                continue;
+            }
+            "format separators on" | "format separators off" => {
+                ctx.format.separators = string.trim() == "format separators on";
+                continue;
+            }
+            // `format (...)` is still a call of the builtin
+            command
+                if command.starts_with("format ")
+                    && command["format ".len()..].starts_with(|c: char| c.is_ascii_lowercase()) =>
+            {
+                match command["format ".len()..].parse() {
+                    Ok(notation) => ctx.format.notation = notation,
+                    Err(err) => println!("{err}."),
+                }
+                continue;
+            }
             "clear" => {
                 clearscreen();
                 continue;
@@ -101,22 +231,50 @@ You can also enter expressions, variable declarations, or function definitions d
             // SAFETY
             // Преобразование в 'static безопасно, так как мы храним строки,
             // пока программа выполняется
-            let span = unsafe { transmute::<_, Span<'static>>(Span::new(&input_rc)) };
+            let span = unsafe { transmute::<Span<'_>, Span<'static>>(Span::new(&input_rc)) };
             storage.push(input_rc);
             span
         };
-        match program(span) {
+        match ctx.program(span) {
             Ok((_, program)) => {
                 match program {
-                    Program::Expression(token) => match ctx.evaluate_expression(&token) {
-                        Ok(result) => println!("{result}"),
-                        Err(err) => println!("{err}"),
+                    Program::Expression(token) => match derive_call(&token) {
+                        Some((name, var)) => match ctx.derive(&name, &var) {
+                            Ok(func) => {
+                                println!("{func}");
+                                ctx.funcs
+                                    .insert(func.data.ident.data.0.clone(), Func::Custom(func));
+                            }
+                            Err(err) => println!("{err}"),
+                        },
+                        None => match ctx.evaluate_expression(&token) {
+                            Ok(result) => println!("{}", ctx.display(&result)),
+                            Err(err) => println!("{err}"),
+                        },
                     },
                     Program::Func(token) => {
-                        ctx.funcs
-                            .insert(token.data.ident.data.0.clone(), Func::Custom(token));
+                        ctx.define(token);
                         println!("Ok!")
                     }
+                    Program::Piecewise(token) => {
+                        let name = token.data.ident.data.0.clone();
+                        ctx.define_piecewise(token);
+                        match ctx.funcs.get(&name) {
+                            Some(Func::Piecewise(func)) if !func.data.is_exhaustive() => {
+                                println!("Ok! Some arguments match no clause of '{name}' yet.")
+                            }
+                            _ => println!("Ok!"),
+                        }
+                    }
+                    Program::Comment(_) => {}
+                    statement @ (Program::If(_)
+                    | Program::While(_)
+                    | Program::For(_)
+                    | Program::Import(_)) => {
+                        if let Err(err) = ctx.execute(&statement) {
+                            println!("{err}");
+                        }
+                    }
                     Program::Var(token) => {
                         let cow = match &token.data.expr {
                             VarAssignExpr::Expression(token) => Cow::Borrowed(token),
@@ -131,10 +289,10 @@ You can also enter expressions, variable declarations, or function definitions d
                             }
                         };
                         match ctx.evaluate_expression(&cow) {
-                            Ok(result) => {
-                                ctx.vars.insert(token.data.ident.data.0.clone(), result);
-                                println!("{result}");
-                            }
+                            Ok(result) => match ctx.assign(&token, result.clone()) {
+                                Ok(()) => println!("{}", ctx.display(&result)),
+                                Err(err) => println!("{err}"),
+                            },
                             Err(err) => {
                                 println!("{err}");
                             }
@@ -162,25 +320,43 @@ You can also enter expressions, variable declarations, or function definitions d
     }
 }
 
-fn execute_main() {
+fn execute_main(options: Options) {
     let mut buffer = String::new();
     stdin().read_to_string(&mut buffer).unwrap();
-    let mut ctx = Context::new();
+    let mut ctx = options.context();
     let span = Span::new(&buffer);
-    match many1(program).parse(span) {
+    match ctx.script(span) {
         Ok((_, programs)) => {
             for program in programs {
                 match program {
-                    Program::Expression(token) => match ctx.evaluate_expression(&token) {
-                        Err(err) => {
+                    Program::Expression(token) => {
+                        if let Some((name, var)) = derive_call(&token) {
+                            match ctx.derive(&name, &var) {
+                                Ok(func) => {
+                                    ctx.funcs
+                                        .insert(func.data.ident.data.0.clone(), Func::Custom(func));
+                                }
+                                Err(err) => {
+                                    println!("{err}");
+                                    return;
+                                }
+                            }
+                        } else if let Err(err) = ctx.evaluate_expression(&token) {
+                            println!("{err}");
+                            return;
+                        }
+                    }
+                    Program::Func(token) => ctx.define(token),
+                    Program::Piecewise(token) => ctx.define_piecewise(token),
+                    Program::Comment(_) => {}
+                    statement @ (Program::If(_)
+                    | Program::While(_)
+                    | Program::For(_)
+                    | Program::Import(_)) => {
+                        if let Err(err) = ctx.execute(&statement) {
                             println!("{err}");
                             return;
                         }
-                        _ => {}
-                    },
-                    Program::Func(token) => {
-                        ctx.funcs
-                            .insert(token.data.ident.data.0.clone(), Func::Custom(token));
                     }
                     Program::Var(token) => {
                         let cow = match &token.data.expr {
@@ -195,14 +371,12 @@ fn execute_main() {
                                 }
                             }
                         };
-                        match ctx.evaluate_expression(&cow) {
-                            Ok(result) => {
-                                ctx.vars.insert(token.data.ident.data.0.clone(), result);
-                            }
-                            Err(err) => {
-                                println!("{err}");
-                                return;
-                            }
+                        if let Err(err) = ctx
+                            .evaluate_expression(&cow)
+                            .and_then(|result| ctx.assign(&token, result))
+                        {
+                            println!("{err}");
+                            return;
                         }
                     }
                 };
@@ -222,46 +396,255 @@ fn execute_main() {
     }
 }
 
+/// Script files under `path` in order, or `path` itself if it is a file.
+fn script_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            script_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == "ne") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the test file at `path` and describes its failures. Statements after
+/// a failed assertion still run, while any other error ends the file.
+fn test_file(path: &Path, options: &Options) -> Vec<String> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => return vec![format!("{}: {err}", path.display())],
+    };
+    let at = |pos: Span, message: &dyn std::fmt::Display| {
+        format!(
+            "{}:{}:{}: {}",
+            path.display(),
+            pos.location_line(),
+            pos.get_column(),
+            message
+        )
+    };
+    let mut ctx = options.context();
+    ctx.enter_file(path);
+    let programs = match ctx.script(Span::new(&source)) {
+        Ok((_, programs)) => programs,
+        Err(Err::Error(err) | Err::Failure(err)) => return vec![at(err.input, &err.message)],
+        Err(Err::Incomplete(_)) => {
+            return vec![format!("{}: Unexpected end of file", path.display())];
+        }
+    };
+    let mut failures = vec![];
+    for program in &programs {
+        match ctx.execute(program) {
+            Ok(()) => {}
+            // Errors of imports are located in the imported file
+            Err(ExecuteError::Import(err)) => {
+                failures.push(err.to_string());
+                break;
+            }
+            Err(err) => match &err {
+                ExecuteError::Evaluate(EvaluateExpressionError::AssertionFailed(call, _)) => {
+                    failures.push(at(call.pos, &err))
+                }
+                _ => {
+                    failures.push(at(program.pos(), &err));
+                    break;
+                }
+            },
+        }
+    }
+    failures
+}
+
+/// Runs every script under `path` as a test and prints a summary. Returns
+/// whether all of them passed.
+fn test_main(path: &Path, options: &Options) -> bool {
+    let mut files = vec![];
+    if let Err(err) = script_files(path, &mut files) {
+        println!("{}: {err}", path.display());
+        return false;
+    }
+    if files.is_empty() {
+        println!("No '.ne' files in {}.", path.display());
+        return false;
+    }
+    let mut failed = 0;
+    for file in &files {
+        let failures = test_file(file, options);
+        if failures.is_empty() {
+            println!("test {} ... ok", file.display());
+        } else {
+            failed += 1;
+            println!("test {} ... FAILED", file.display());
+            for failure in failures {
+                println!("  {failure}");
+            }
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        files.len() - failed,
+        failed
+    );
+    failed == 0
+}
+
 fn main() {
-    if let Some(arg) = args().nth(1) {
+    let mut options = Options::default();
+    let mut execute = false;
+    let mut test = None;
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
         match arg.as_str() {
-            "-e" | "--execute" => {
-                execute_main();
+            "-e" | "--execute" => execute = true,
+            "test" => match args.next() {
+                Some(path) => test = Some(PathBuf::from(path)),
+                None => {
+                    println!("'test' expects a file or directory.");
+                    return;
+                }
+            },
+            "--strict" => options.strict = true,
+            "--complex" => options.complex = true,
+            "--interval" => options.interval = true,
+            "--decimal" => {
+                options.decimal.get_or_insert_default();
             }
+            "--scale" => match args.next().map(|scale| scale.parse()) {
+                Some(Ok(scale)) if scale <= MAX_SCALE => {
+                    options.decimal.get_or_insert_default().scale = scale
+                }
+                _ => {
+                    println!("'--scale' expects an integer between 0 and {MAX_SCALE}.");
+                    return;
+                }
+            },
+            "--rounding" => match args.next().map(|rounding| rounding.parse()) {
+                Some(Ok(rounding)) => options.decimal.get_or_insert_default().rounding = rounding,
+                Some(Err(err)) => {
+                    println!("{err}.");
+                    return;
+                }
+                None => {
+                    println!("'--rounding' expects a rounding mode.");
+                    return;
+                }
+            },
+            "--seed" => match args.next().map(|seed| seed.parse()) {
+                Some(Ok(seed)) => options.seed = Some(seed),
+                _ => {
+                    println!("'--seed' expects a non-negative integer.");
+                    return;
+                }
+            },
+            "--format" => match args.next().map(|notation| notation.parse()) {
+                Some(Ok(notation)) => options.format.notation = notation,
+                Some(Err(err)) => {
+                    println!("{err}.");
+                    return;
+                }
+                None => {
+                    println!("'--format' expects a notation.");
+                    return;
+                }
+            },
+            "--separators" => options.format.separators = true,
+            "--fuel" => match args.next().map(|fuel| fuel.parse()) {
+                Some(Ok(fuel)) => options.fuel = Some(fuel),
+                _ => {
+                    println!("'--fuel' expects a non-negative integer.");
+                    return;
+                }
+            },
             "-h" | "--help" => {
                 println!(
                     "Usage: nelang [OPTIONS]
+       nelang test PATH [OPTIONS]
 
 NeLang - Simple Expression Interpreter
 
 Options:
   -e, --execute    Execute a program passed through the pipe.
+      --strict     Keep booleans distinct from numbers and reject mixing them.
+      --complex    Let 'sqrt' and 'ln' of negative numbers return complex values.
+      --interval   Evaluate numbers as intervals enclosing the exact result.
+      --decimal    Evaluate numbers as decimals rounded to a fixed scale.
+      --scale N    Number of decimal places in decimal mode (default 2).
+      --rounding M Rounding in decimal mode: half-even (default), half-up or truncate.
+      --seed N     Seed the random builtins so that runs are reproducible.
+      --format F   Number notation: auto (default), fixed:N, sig:N, sci[:N], eng[:N], hex or bin.
+      --separators Group the digits of numbers in thousands.
+      --fuel N     Limit the statements and loop iterations a statement may run.
   -h, --help       Display this help message.
 
 Description:
   Call the program without arguments to enter REPL mode.
   To execute a program, pass it through the pipe with the '-e' or '--execute' flag.
+  'test PATH' runs every '.ne' file under PATH and reports the failed 'assert' calls,
+  exiting with a non-zero status if any fail.
 
 Syntax rules:
   expr           = term (operator term)* | ternary
-  term           = number | ident | func_call | '(' expr ')' | unary_operator term
-  func_call      = ident '(' args ')' '=' expr
-  var            = ident '=' expr
+  term           = number | string | bool | interval | list | let | qualified | func_call | '(' expr ')' | unary_operator term
+  let            = 'let' ident '=' expr (',' ident '=' expr)* 'in' expr
+  if             = 'if' expr block ('else' (block | if))?
+  while          = 'while' expr block
+  for            = 'for' ident 'in' (expr '..' expr | expr) block
+  block          = '{{' (statement EOL)* statement? '}}'
+  import         = 'import' string ('as' ident)?
+  string         = '\"' (char | '\\' escape)* '\"'
+  func_call      = 'memo'? ident '(' args ')' '=' expr
+  piecewise      = 'memo'? clause (';' clause)*
+  clause         = ident '(' params ')' ('=' expr | ('|' (expr | 'otherwise') '=' expr)+)
+  params         = param (',' param)*
+  param          = ident | '-'? number | string | bool
+  var            = 'const'? ident '=' expr
   args           = ident (',' ident)*
-  operator       = '+' | '-' | '*' | '/' | '%' | '<' | '<=' | '==' | '!=' | '>=' | '>'
+  ident          = (letter | '_') (letter | digit | '_')* \"'\"*
+  qualified      = ident ('.' ident)*
+  operator       = '+' | '-' | '*' | '/' | '%' | '<' | '<=' | '==' | '!=' | '>=' | '>' | symbol
+  infix          = ('infixl' | 'infixr') digit symbol '(' ident ',' ident ')' '=' expr
+  symbol         = ('!' | '$' | '%' | '&' | '*' | '+' | '-' | '/' | '<' | '=' | '>' | '@' | '^' | '|' | '~')+
+  bool           = 'true' | 'false'
+  interval       = '[' expr '..' expr ']'
+  list           = '[' (expr (',' expr)*)? ']'
+  number         = (real | radix_int) 'i'? unit?
+  real           = (digits ('.' digits)? | '.' digits) (('e' | 'E') ('+' | '-')? digits)?
+  radix_int      = '0x' hex_digits | '0o' oct_digits | '0b' bin_digits
+  digits         = digit ('_'? digit)*
+  unit           = ident ('^' int)? (('*' | '/') ident ('^' int)?)*
   unary_operator = '+' | '-' | '!'
+  comment        = '#' char* | '/*' char* '*/'
   ternary        = expr '?' expr ':' expr
 
 Note:
   For ternary expressions, the condition should be wrapped in parentheses if it is complex.
   Example: (x > 0) ? x : -x"
                 );
+                return;
             }
             other => {
-                println!("Undefined argument '{other}'. Use '-h' or '--help' to print help.")
+                println!("Undefined argument '{other}'. Use '-h' or '--help' to print help.");
+                return;
             }
         }
+    }
+    if let Some(path) = test {
+        if !test_main(&path, &options) {
+            exit(1);
+        }
+    } else if execute {
+        execute_main(options);
     } else {
-        repl_main();
+        repl_main(options);
     }
 }
@@ -0,0 +1,79 @@
+use std::fs;
+use std::process::Command;
+
+use nelang::lang::{Context, EvaluateExpressionError, ExecuteError};
+
+mod common;
+
+use common::run;
+
+#[test]
+fn test_assertions() {
+    let mut ctx = Context::new();
+    run(
+        &mut ctx,
+        "x = 2\nassert(x == 2)\nassert(x > 1, \"x is large\")\n",
+    )
+    .unwrap();
+    let err = run(&mut ctx, "y = 1\nassert(x == 3, \"x should be 3\")\n").unwrap_err();
+    let ExecuteError::Evaluate(EvaluateExpressionError::AssertionFailed(call, Some(_))) = &err
+    else {
+        panic!("Expected assertion failure, got {err:?}");
+    };
+    assert_eq!((call.pos.location_line(), call.pos.get_column()), (2, 1));
+    assert_eq!(err.to_string(), "Assertion failed: x should be 3");
+    let err = run(&mut ctx, "for i in 0..3 { assert(i < 2) }\n").unwrap_err();
+    assert_eq!(err.to_string(), "Assertion failed: 'assert(i < 2)'");
+}
+
+#[test]
+fn test_assertion_arguments() {
+    let mut ctx = Context::new();
+    assert!(run(&mut ctx, "assert(1, 2)\n").is_err());
+    ctx.strict = true;
+    let err = run(&mut ctx, "assert(1)\n").unwrap_err();
+    assert!(matches!(
+        err,
+        ExecuteError::Evaluate(EvaluateExpressionError::TypeMismatch(_, "bool", "number"))
+    ));
+    run(&mut ctx, "assert(true)\n").unwrap();
+}
+
+#[test]
+fn test_runner() {
+    let dir = std::env::temp_dir().join(format!("nelang-tests-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("lib/circuit.ne"),
+        "parallel(a, b) = a * b / (a + b)\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("passing.ne"),
+        "import \"lib/circuit.ne\"\nassert(circuit.parallel(4, 4) == 2)\n",
+    )
+    .unwrap();
+    let nelang = env!("CARGO_BIN_EXE_nelang");
+    let output = Command::new(nelang).arg("test").arg(&dir).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.ends_with("test result: ok. 2 passed; 0 failed\n"));
+
+    fs::write(
+        dir.join("failing.ne"),
+        "x = 1\nassert(x == 2, \"first\")\n  assert(x == 3)\ny = missing\nassert(false)\n",
+    )
+    .unwrap();
+    let output = Command::new(nelang).arg("test").arg(&dir).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let file = dir.join("failing.ne").display().to_string();
+    assert!(stdout.contains(&format!("test {file} ... FAILED")));
+    assert!(stdout.contains(&format!("{file}:2:1: Assertion failed: first")));
+    assert!(stdout.contains(&format!("{file}:3:3: Assertion failed: 'assert(x == 3)'")));
+    // Other errors end the file
+    assert!(stdout.contains(&format!("{file}:4:1: Undefined variable: 'missing'")));
+    assert!(!stdout.contains(":5:1:"));
+    assert!(stdout.ends_with("test result: FAILED. 2 passed; 1 failed\n"));
+}
@@ -0,0 +1,55 @@
+use nelang::lang::{Context, EvaluateExpressionError, Value};
+
+mod common;
+
+use common::{define, eval, evaluate};
+
+#[test]
+fn test_diff_polynomial() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "f(x) = x * x * x + 2 * x\n");
+    assert_eq!(eval(&mut ctx, "diff(f, 2)\n"), 14.);
+    assert_eq!(eval(&mut ctx, "diff(f, 0)\n"), 2.);
+    define(&mut ctx, "c(x) = 5\n");
+    assert_eq!(eval(&mut ctx, "diff(c, 1)\n"), 0.);
+}
+
+#[test]
+fn test_diff_builtins_and_branches() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "diff(sin, 0)\n"), 1.);
+    assert_eq!(eval(&mut ctx, "diff(exp, 0)\n"), 1.);
+    define(&mut ctx, "f(x) = ln(x * x)\n");
+    assert_eq!(eval(&mut ctx, "diff(f, 2)\n"), 1.);
+    define(&mut ctx, "g(x) = (x > 0) ? x * x : -x\n");
+    assert_eq!(eval(&mut ctx, "diff(g, 3)\n"), 6.);
+    assert_eq!(eval(&mut ctx, "diff(g, -3)\n"), -1.);
+}
+
+#[test]
+fn test_grad() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "h(x, y) = x * x * y + sin(y)\n");
+    assert_eq!(
+        eval(&mut ctx, "grad(h, 1, 0)\n"),
+        Value::List(vec![Value::Number(0.), Value::Number(2.)].into())
+    );
+}
+
+#[test]
+fn test_diff_errors() {
+    let mut ctx = Context::new();
+    assert!(matches!(
+        evaluate(&mut ctx, "diff(3, 1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(..))
+    ));
+    define(&mut ctx, "f(x, y) = x * y\n");
+    assert!(matches!(
+        evaluate(&mut ctx, "diff(f, 1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(..))
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "diff(sqrt, -1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(..))
+    ));
+}
@@ -0,0 +1,71 @@
+use nelang::lang::{Context, EvaluateExpressionError, Program, Span, Value, program};
+
+mod common;
+
+use common::{eval, evaluate};
+
+#[test]
+fn test_default_mode_keeps_numeric_truthiness() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "true\n"), Value::Bool(true));
+    assert_eq!(eval(&mut ctx, "5 < 10\n"), 1.);
+    assert_eq!(eval(&mut ctx, "!5\n"), 0.);
+    assert_eq!(eval(&mut ctx, "true + 1\n"), 2.);
+    assert_eq!(eval(&mut ctx, "(true == 1) ? 3 : 4\n"), 3.);
+    assert_eq!(eval(&mut ctx, "false ? 3 : 4\n"), 4.);
+}
+
+#[test]
+fn test_strict_mode_booleans() {
+    let mut ctx = Context::new();
+    ctx.strict = true;
+    assert_eq!(eval(&mut ctx, "5 < 10\n"), Value::Bool(true));
+    assert_eq!(eval(&mut ctx, "!(5 < 10)\n"), Value::Bool(false));
+    assert_eq!(eval(&mut ctx, "true == false\n"), Value::Bool(false));
+    assert_eq!(eval(&mut ctx, "(1 == 1) ? 3 : 4\n"), 3.);
+}
+
+#[test]
+fn test_strict_mode_type_mismatch() {
+    let mut ctx = Context::new();
+    ctx.strict = true;
+
+    match evaluate(&mut ctx, "1 + (2 > 1)\n") {
+        Err(EvaluateExpressionError::TypeMismatch(expr, expected, found)) => {
+            assert_eq!(expr.to_string(), "(2 > 1)");
+            assert_eq!((expected, found), ("number", "bool"));
+        }
+        other => panic!("Expected type mismatch, got {other:?}"),
+    }
+
+    match evaluate(&mut ctx, "(1 + 1) ? 5 : 10\n") {
+        Err(EvaluateExpressionError::TypeMismatch(expr, expected, found)) => {
+            assert_eq!(expr.to_string(), "(1 + 1)");
+            assert_eq!(expr.pos.get_column(), 1);
+            assert_eq!((expected, found), ("bool", "number"));
+        }
+        other => panic!("Expected type mismatch, got {other:?}"),
+    }
+
+    assert!(matches!(
+        evaluate(&mut ctx, "!5\n"),
+        Err(EvaluateExpressionError::TypeMismatch(..))
+    ));
+}
+
+#[test]
+fn test_strict_mode_inside_functions() {
+    let mut ctx = Context::new();
+    ctx.strict = true;
+    if let (_, Program::Func(token)) = program(Span::new("inc(x) = x + 1\n")).unwrap() {
+        ctx.funcs.insert(
+            token.data.ident.data.0.clone(),
+            nelang::lang::Func::Custom(token),
+        );
+    }
+    assert_eq!(eval(&mut ctx, "inc(1)\n"), 2.);
+    assert!(matches!(
+        evaluate(&mut ctx, "inc(true)\n"),
+        Err(EvaluateExpressionError::TypeMismatch(..))
+    ));
+}
@@ -0,0 +1,97 @@
+use nelang::lang::{Context, Program, Span, program};
+use nom::Parser;
+use nom::multi::many1;
+
+mod common;
+
+use common::eval;
+
+fn parse(input: &str) -> Vec<Program<'_>> {
+    many1(program).parse(Span::new(input)).unwrap().1
+}
+
+#[test]
+fn test_comments_are_ignored() {
+    let mut ctx = Context::new();
+    let mut evaluate = |input| eval(&mut ctx, input).to_string();
+    assert_eq!(evaluate("-+5  # Unary operations can be chained\n"), "-5");
+    assert_eq!(evaluate("1 /* one */ + /* two */ 2\n"), "3");
+    assert_eq!(evaluate("/* leading */ 4 * 2\n"), "8");
+    assert_eq!(
+        evaluate("\"# not /* a */ comment\"\n"),
+        "# not /* a */ comment"
+    );
+}
+
+#[test]
+fn test_comments_are_preserved() {
+    let source = "# header\nx = 1 # trailing\n/* block\n   spanning lines */\nf(a) = a\n";
+    let programs = parse(source);
+    let kinds: Vec<_> = programs
+        .iter()
+        .map(|program| match program {
+            Program::Comment(comment) => format!("comment {:?}", comment.data.0),
+            Program::Var(_) => "var".to_string(),
+            Program::Func(_) => "func".to_string(),
+            Program::Expression(_) => "expression".to_string(),
+            Program::If(_)
+            | Program::While(_)
+            | Program::For(_)
+            | Program::Piecewise(_)
+            | Program::Import(_) => "statement".to_string(),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "comment \" header\"",
+            "var",
+            "comment \" trailing\"",
+            "comment \" block\\n   spanning lines \"",
+            "func",
+        ]
+    );
+    let Program::Comment(trailing) = &programs[2] else {
+        unreachable!()
+    };
+    assert_eq!(trailing.to_string(), "# trailing");
+    assert_eq!(trailing.pos.location_line(), 2);
+    assert_eq!(trailing.pos.get_column(), 7);
+}
+
+#[test]
+fn test_unterminated_block_comment() {
+    let Err(nom::Err::Failure(err)) = program(Span::new("1 + /* open\n")) else {
+        panic!("expected a failure");
+    };
+    assert_eq!(err.message, "Unterminated block comment");
+    assert_eq!(err.input.get_column(), 5);
+}
+
+#[test]
+fn test_errors_point_at_the_statement() {
+    for (input, column) in [("1 2\n", 3), ("x m\n", 3), ("x = 1 2 # note\n", 7)] {
+        let Err(nom::Err::Error(err) | nom::Err::Failure(err)) = program(Span::new(input)) else {
+            panic!("expected an error for {input:?}");
+        };
+        assert_eq!(err.message, "Expected EOL");
+        assert_eq!(err.input.get_column(), column);
+    }
+}
+
+#[test]
+fn test_block_comments_are_reprinted() {
+    let source =
+        "x = 1 /* one */\n/* own line */\nprint(x) /* a */ /* b */ # c\ny = 1 /* inside */ + 2\n";
+    let comments: Vec<_> = parse(source)
+        .iter()
+        .filter_map(|program| match program {
+            Program::Comment(comment) => Some(comment.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        comments,
+        ["/* one */", "/* own line */", "/* a */", "/* b */", "# c"]
+    );
+}
@@ -0,0 +1,58 @@
+//! Helpers shared by the integration tests. Each test file uses only some.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nelang::lang::{Context, EvaluateExpressionError, ExecuteError, Program, Span, Value};
+
+/// Parses the statements of `input` with the operators of `ctx` and runs them.
+pub fn run<'a>(ctx: &mut Context<'a>, input: &'a str) -> Result<(), ExecuteError<'a>> {
+    for program in ctx.script(Span::new(input)).unwrap().1 {
+        ctx.execute(&program)?;
+    }
+    Ok(())
+}
+
+/// Runs statements that must succeed.
+pub fn define<'a>(ctx: &mut Context<'a>, input: &'a str) {
+    run(ctx, input).unwrap();
+}
+
+/// Evaluates the expression `input`.
+pub fn evaluate<'a>(
+    ctx: &mut Context<'a>,
+    input: &'a str,
+) -> Result<Value, EvaluateExpressionError<'a>> {
+    let Program::Expression(expr) = ctx.program(Span::new(input)).unwrap().1 else {
+        panic!("Expected expression");
+    };
+    ctx.evaluate_expression(&expr)
+}
+
+/// Evaluates an expression that must succeed.
+pub fn eval<'a>(ctx: &mut Context<'a>, input: &'a str) -> Value {
+    evaluate(ctx, input).unwrap()
+}
+
+/// Fresh directory under the system temp directory, removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("nelang-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,58 @@
+use nelang::lang::{Complex, Context, EvaluateExpressionError, Value};
+
+mod common;
+
+use common::{eval, evaluate};
+
+#[test]
+fn test_imaginary_literals_and_arithmetic() {
+    let mut ctx = Context::new();
+    assert_eq!(
+        eval(&mut ctx, "3 + 4i\n"),
+        Value::Complex(Complex::new(3., 4.))
+    );
+    assert_eq!(eval(&mut ctx, "2.5i\n").to_string(), "2.5i");
+    assert_eq!(eval(&mut ctx, "(1 + 2i) * (3 - 1i)\n").to_string(), "5+5i");
+    assert_eq!(eval(&mut ctx, "(1 + 1i) / 1i\n").to_string(), "1-i");
+    // Products without an imaginary part collapse back to real numbers
+    assert_eq!(eval(&mut ctx, "1i * 1i\n"), -1.);
+    assert_eq!(eval(&mut ctx, "1i == 1i\n"), 1.);
+    assert!(matches!(
+        evaluate(&mut ctx, "1i < 2i\n"),
+        Err(EvaluateExpressionError::InvalidOperands(_))
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "1 / (0i)\n"),
+        Err(EvaluateExpressionError::DivisionByZero(_))
+    ));
+}
+
+#[test]
+fn test_complex_builtins() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "re(3 + 4i)\n"), 3.);
+    assert_eq!(eval(&mut ctx, "im(3 + 4i)\n"), 4.);
+    assert_eq!(eval(&mut ctx, "abs(3 + 4i)\n"), 5.);
+    assert_eq!(eval(&mut ctx, "abs(-2)\n"), 2.);
+    assert_eq!(eval(&mut ctx, "conj(3 + 4i)\n").to_string(), "3-4i");
+    assert_eq!(eval(&mut ctx, "arg(1i)\n"), std::f64::consts::FRAC_PI_2);
+    assert_eq!(eval(&mut ctx, "sqrt(-3 + 4i)\n").to_string(), "1+2i");
+}
+
+#[test]
+fn test_complex_mode() {
+    let mut ctx = Context::new();
+    assert!(matches!(
+        evaluate(&mut ctx, "sqrt(-1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(_, _))
+    ));
+    assert!(evaluate(&mut ctx, "ln(-1)\n").is_err());
+
+    ctx.complex = true;
+    assert_eq!(eval(&mut ctx, "sqrt(-1)\n").to_string(), "i");
+    assert_eq!(
+        eval(&mut ctx, "ln(-1)\n"),
+        Value::Complex(Complex::new(0., std::f64::consts::PI))
+    );
+    assert_eq!(eval(&mut ctx, "sqrt(16)\n"), 4.);
+}
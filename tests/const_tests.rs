@@ -0,0 +1,65 @@
+use nelang::lang::{Context, EvaluateExpressionError, ExecuteError, Value};
+
+mod common;
+
+use common::run;
+
+#[test]
+fn test_const_definitions() {
+    let mut ctx = Context::new();
+    run(&mut ctx, "const g = 9.81\nweight = 70 * g\n").unwrap();
+    assert!(ctx.is_const("g"));
+    assert!(!ctx.is_const("weight"));
+    assert_eq!(ctx.vars.get("weight"), Some(&Value::Number(70. * 9.81)));
+    run(&mut ctx, "weight = 0\n").unwrap();
+    assert_eq!(ctx.vars.get("weight"), Some(&Value::Number(0.)));
+}
+
+#[test]
+fn test_reassignment_is_rejected() {
+    let mut ctx = Context::new();
+    let source = "const g = 9.81\nx = 1\ng = 10\n";
+    let err = run(&mut ctx, source).unwrap_err();
+    let ExecuteError::Evaluate(EvaluateExpressionError::ConstReassignment(ident, Some(definition))) =
+        &err
+    else {
+        panic!("Expected reassignment error, got {err:?}");
+    };
+    assert_eq!((ident.pos.location_line(), ident.pos.get_column()), (3, 1));
+    assert_eq!(definition.location_line(), 1);
+    assert_eq!(
+        err.to_string(),
+        "Cannot reassign constant 'g' at line 3, column 1; it was defined at line 1, column 1: 'const g = 9.81'"
+    );
+    assert_eq!(ctx.vars.get("g"), Some(&Value::Number(9.81)));
+    assert!(run(&mut ctx, "const g = 1\n").is_err());
+    assert!(run(&mut ctx, "for g in 0..2 { y = g }\n").is_err());
+    assert!(run(&mut ctx, "if 1 { g = 2 }\n").is_err());
+    // Local bindings may shadow constants
+    run(&mut ctx, "y = let g = 2 in g * 3\n").unwrap();
+    assert_eq!(ctx.vars.get("y"), Some(&Value::Number(6.)));
+}
+
+#[test]
+fn test_host_constants() {
+    let mut ctx = Context::new();
+    ctx.define_const("pi", Value::Number(std::f64::consts::PI));
+    run(&mut ctx, "area = pi * 2 * 2\n").unwrap();
+    assert_eq!(
+        ctx.vars.get("area"),
+        Some(&Value::Number(std::f64::consts::PI * 4.))
+    );
+    let err = run(&mut ctx, "pi = 3\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Cannot reassign constant 'pi' at line 1, column 1; it is built in"
+    );
+}
+
+#[test]
+fn test_const_as_variable_name() {
+    let mut ctx = Context::new();
+    run(&mut ctx, "const = 5\nconst = const + 1\n").unwrap();
+    assert_eq!(ctx.vars.get("const"), Some(&Value::Number(6.)));
+    assert!(!ctx.is_const("const"));
+}
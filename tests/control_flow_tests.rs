@@ -0,0 +1,77 @@
+use nelang::lang::{Context, EvaluateExpressionError, ExecuteError, Span, Value, script};
+
+mod common;
+
+use common::run;
+
+#[test]
+fn test_if_else_chains() {
+    let source = "
+for n in [95, 75, 40] {
+    if n >= 90 {
+        a = n
+    } else if n >= 70 {
+        b = n
+    } else {
+        c = n
+    }
+}
+if 0 { never = 1 }
+";
+    let mut ctx = Context::new();
+    run(&mut ctx, source).unwrap();
+    assert_eq!(ctx.vars.get("a"), Some(&Value::Number(95.)));
+    assert_eq!(ctx.vars.get("b"), Some(&Value::Number(75.)));
+    assert_eq!(ctx.vars.get("c"), Some(&Value::Number(40.)));
+    assert_eq!(ctx.vars.get("never"), None);
+}
+
+#[test]
+fn test_loops() {
+    let source = "
+total = 0
+for i in 1..5 { total = total + i }
+n = 1
+while n < 100 {
+    n = n * 2
+}
+empty = 0
+for i in 3..3 { empty = 1 }
+";
+    let mut ctx = Context::new();
+    run(&mut ctx, source).unwrap();
+    assert_eq!(ctx.vars.get("total"), Some(&Value::Number(10.)));
+    assert_eq!(ctx.vars.get("n"), Some(&Value::Number(128.)));
+    assert_eq!(ctx.vars.get("empty"), Some(&Value::Number(0.)));
+    assert_eq!(ctx.vars.get("i"), Some(&Value::Number(4.)));
+}
+
+#[test]
+fn test_fuel_stops_runaway_loops() {
+    let mut ctx = Context::new();
+    ctx.fuel = Some(1000);
+    let err = run(&mut ctx, "n = 0\nwhile true { n = n + 1 }\n").unwrap_err();
+    assert!(matches!(err, ExecuteError::OutOfFuel(_)));
+    assert!(err.to_string().starts_with("Out of fuel at line 2"));
+    // Every statement and iteration takes a step, and each statement gets
+    // the whole budget
+    assert_eq!(ctx.vars.get("n"), Some(&Value::Number(499.)));
+
+    ctx.fuel = Some(7);
+    run(&mut ctx, "for i in 0..3 { x = i }\n").unwrap();
+    assert!(run(&mut ctx, "for i in 0..4 { x = i }\n").is_err());
+}
+
+#[test]
+fn test_errors() {
+    let mut ctx = Context::new();
+    let err = run(&mut ctx, "for x in 5 { y = x }\n").unwrap_err();
+    assert!(matches!(
+        err,
+        ExecuteError::Evaluate(EvaluateExpressionError::TypeMismatch(_, "list", "number"))
+    ));
+    let err = run(&mut ctx, "if 1 { z = missing }\n").unwrap_err();
+    assert_eq!(err.to_string(), "Undefined variable: 'missing'");
+    assert!(script(Span::new("if 1 { z = 1\n")).is_err());
+    assert!(script(Span::new("while 1 z = 1\n")).is_err());
+}
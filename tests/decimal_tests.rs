@@ -0,0 +1,83 @@
+use nelang::lang::{Context, Decimal, DecimalMode, EvaluateExpressionError, Rounding, Value};
+
+mod common;
+
+use common::{eval, evaluate};
+
+fn decimal_context<'a>(scale: u32, rounding: Rounding) -> Context<'a> {
+    let mut ctx = Context::new();
+    ctx.decimal = Some(DecimalMode { scale, rounding });
+    ctx
+}
+
+#[test]
+fn test_decimal_arithmetic() {
+    let mut ctx = decimal_context(2, Rounding::HalfEven);
+    assert_eq!(eval(&mut ctx, "0.1 + 0.2\n").to_string(), "0.30");
+    assert_eq!(eval(&mut ctx, "19.99 * 3\n").to_string(), "59.97");
+    assert_eq!(eval(&mut ctx, "10 / 3\n").to_string(), "3.33");
+    assert_eq!(
+        eval(&mut ctx, "1.5 - 2\n"),
+        Value::Decimal(Decimal::new(-50, 2))
+    );
+    assert!(matches!(
+        evaluate(&mut ctx, "1 / 0\n"),
+        Err(EvaluateExpressionError::DivisionByZero(_))
+    ));
+}
+
+#[test]
+fn test_rounding_modes() {
+    let cases = [
+        (Rounding::HalfEven, ["0.12", "0.38", "-0.12"]),
+        (Rounding::HalfUp, ["0.13", "0.38", "-0.13"]),
+        (Rounding::Truncate, ["0.12", "0.37", "-0.12"]),
+    ];
+    for (rounding, expected) in cases {
+        let mut ctx = decimal_context(2, rounding);
+        for (input, expected) in ["0.125 + 0\n", "0.375 + 0\n", "-0.125\n"]
+            .into_iter()
+            .zip(expected)
+        {
+            assert_eq!(evaluate(&mut ctx, input).unwrap().to_string(), expected);
+        }
+    }
+    let mut ctx = decimal_context(0, Rounding::HalfEven);
+    assert_eq!(eval(&mut ctx, "2.5 + 0\n").to_string(), "2");
+    // The product has 29 fractional digits, beyond what a decimal can hold
+    for (rounding, expected) in [(Rounding::Truncate, "1"), (Rounding::HalfEven, "2")] {
+        let mut ctx = decimal_context(28, rounding);
+        assert_eq!(
+            eval(&mut ctx, "0.00000000000001 * 0.000000000000015\n").to_string(),
+            format!("0.{expected:0>28}")
+        );
+    }
+}
+
+#[test]
+fn test_round() {
+    let mut ctx = Context::new();
+    // 2.675 is stored in binary as 2.67499999...
+    assert_eq!(eval(&mut ctx, "round(2.675, 2)\n"), 2.68);
+    assert_eq!(eval(&mut ctx, "round(2.665, 2)\n"), 2.66);
+    assert_eq!(eval(&mut ctx, "round(2.5)\n"), 2.);
+    assert!(matches!(
+        evaluate(&mut ctx, "round(1, -1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(..))
+    ));
+    let mut ctx = decimal_context(4, Rounding::HalfUp);
+    assert_eq!(eval(&mut ctx, "round(2.665, 2)\n").to_string(), "2.6700");
+}
+
+#[test]
+fn test_comparisons_stay_booleans() {
+    let mut ctx = decimal_context(4, Rounding::HalfEven);
+    assert_eq!(eval(&mut ctx, "0.1 + 0.2 == 0.3\n"), Value::Bool(true));
+    assert_eq!(eval(&mut ctx, "!(2 < 1)\n").to_string(), "true");
+    assert_eq!(
+        eval(&mut ctx, "[1 > 2, 1]\n").to_string(),
+        "[false, 1.0000]"
+    );
+    // Booleans still count as numbers outside of strict mode
+    assert_eq!(eval(&mut ctx, "(1 < 2) + 1\n").to_string(), "2.0000");
+}
@@ -0,0 +1,78 @@
+use nelang::lang::{Context, Notation, NumberFormat, Program, Span, Value, program};
+
+fn display<'a>(ctx: &Context<'a>, input: &'a str) -> String {
+    match program(Span::new(input)).unwrap().1 {
+        Program::Expression(token) => ctx.display(&ctx.evaluate_expression(&token).unwrap()),
+        _ => unreachable!(),
+    }
+}
+
+fn format(notation: Notation, number: f64) -> String {
+    NumberFormat {
+        notation,
+        separators: false,
+    }
+    .format(number)
+}
+
+#[test]
+fn test_notations() {
+    assert_eq!(format(Notation::Auto, 0.1 + 0.2), "0.30000000000000004");
+    assert_eq!(format(Notation::Fixed(2), 2. / 3.), "0.67");
+    assert_eq!(format(Notation::Significant(3), 0.1 + 0.2), "0.3");
+    assert_eq!(format(Notation::Significant(2), 123456.), "120000");
+    assert_eq!(format(Notation::Scientific(None), 1500.), "1.5e3");
+    assert_eq!(format(Notation::Scientific(Some(2)), 0.00123), "1.23e-3");
+    assert_eq!(format(Notation::Engineering(None), 47000.), "47e3");
+    assert_eq!(format(Notation::Engineering(Some(1)), 0.0002), "200.0e-6");
+    assert_eq!(format(Notation::Engineering(Some(0)), 999.9), "1e3");
+}
+
+#[test]
+fn test_integer_radix() {
+    assert_eq!(format(Notation::Hex, 255.), "0xff");
+    assert_eq!(format(Notation::Hex, -16.), "-0x10");
+    assert_eq!(format(Notation::Binary, 5.), "0b101");
+    assert_eq!(format(Notation::Binary, 0.5), "0.5");
+}
+
+#[test]
+fn test_separators() {
+    let mut fmt = NumberFormat {
+        notation: Notation::Fixed(2),
+        separators: true,
+    };
+    assert_eq!(fmt.format(1234567.891), "1,234,567.89");
+    assert_eq!(fmt.format(-999.), "-999.00");
+    fmt.notation = Notation::Auto;
+    assert_eq!(fmt.format(-1000000.), "-1,000,000");
+    assert_eq!(
+        fmt.format_value(&Value::List([1e4.into(), true.into()].into())),
+        "[10,000, true]"
+    );
+}
+
+#[test]
+fn test_context_format() {
+    let mut ctx = Context::new();
+    ctx.format.notation = "fixed 3".parse().unwrap();
+    assert_eq!(display(&ctx, "1 / 3\n"), "0.333");
+    assert_eq!(display(&ctx, "[1, 2] \n"), "[1.000, 2.000]");
+    assert_eq!(display(&ctx, "\"a\" + \"b\"\n"), "ab");
+    assert_eq!("sci:4".parse(), Ok(Notation::Scientific(Some(4))));
+    assert!("fixed".parse::<Notation>().is_err());
+    assert!("sig 0".parse::<Notation>().is_err());
+    assert!("octal".parse::<Notation>().is_err());
+}
+
+#[test]
+fn test_compound_values() {
+    let mut ctx = Context::new();
+    ctx.format.notation = "fixed 2".parse().unwrap();
+    assert_eq!(display(&ctx, "1 / 3 + 2i / 3\n"), "0.33+0.67i");
+    assert_eq!(display(&ctx, "-1 / 3 * 1i\n"), "-0.33i");
+    assert_eq!(display(&ctx, "5 km / 3\n"), "1.67 km");
+    assert_eq!(display(&ctx, "[1 .. 2] / 3\n"), "[0.33 .. 0.67]");
+    ctx.format.notation = Notation::Auto;
+    assert_eq!(display(&ctx, "1 + 2i\n"), "1+2i");
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::PathBuf;
+
+use nelang::lang::{Context, Func};
+
+mod common;
+
+use common::{eval, run};
+
+/// Writes `files` into a fresh directory under the system temp directory.
+fn modules(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nelang-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (file, source) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_namespaced_definitions() {
+    let dir = modules(
+        "namespaces",
+        &[(
+            "geometry.ne",
+            "const scale = 2.5\nsq(x) = x * x\narea(r) = 3 * sq(r)\nside = sq(3)\n",
+        )],
+    );
+    let source = format!(
+        "import \"{0}\"\nimport \"{0}\" as geo\nsq(x) = 0\n",
+        dir.join("geometry.ne").display()
+    );
+    let mut ctx = Context::new();
+    run(&mut ctx, &source).unwrap();
+    // Module functions call each other, not the importer's 'sq'
+    assert_eq!(eval(&mut ctx, "geometry.area(2)\n"), 12.0);
+    assert_eq!(eval(&mut ctx, "geo.sq(4) + sq(4)\n"), 16.0);
+    assert_eq!(eval(&mut ctx, "geo.side + geo.scale\n"), 11.5);
+    assert!(ctx.is_const("geo.scale"));
+    assert!(matches!(
+        ctx.funcs.get("geo.area"),
+        Some(Func::Imported { .. })
+    ));
+    assert!(!ctx.funcs.contains_key("geo.sqrt"));
+}
+
+#[test]
+fn test_nested_imports() {
+    let dir = modules(
+        "nested",
+        &[
+            ("main.ne", "import \"lib/solids.ne\" as solids\n"),
+            (
+                "lib/solids.ne",
+                "import \"shapes.ne\"\nbox(a) = shapes.cube(a) + 1\n",
+            ),
+            ("lib/shapes.ne", "cube(x) = x * x * x\n"),
+        ],
+    );
+    let mut ctx = Context::new();
+    ctx.import(&dir.join("main.ne"), "main").unwrap();
+    assert_eq!(eval(&mut ctx, "main.solids.box(2)\n"), 9.0);
+    assert_eq!(eval(&mut ctx, "main.solids.shapes.cube(3)\n"), 27.0);
+}
+
+#[test]
+fn test_import_cycles() {
+    let dir = modules(
+        "cycles",
+        &[("a.ne", "import \"b.ne\"\n"), ("b.ne", "import \"a.ne\"\n")],
+    );
+    let mut ctx = Context::new();
+    let err = ctx.import(&dir.join("a.ne"), "a").unwrap_err();
+    let [a, b] = ["a.ne", "b.ne"].map(|file| dir.join(file).display().to_string());
+    assert_eq!(err.message, format!("Import cycle: {a} -> {b} -> {a}"));
+}
+
+#[test]
+fn test_errors_name_the_module() {
+    let dir = modules(
+        "errors",
+        &[
+            ("broken.ne", "x = 1\ny = (2 +\n"),
+            ("failing.ne", "ok = 1\nbad = missing\n"),
+            ("my-lib.ne", "x = 1\n"),
+        ],
+    );
+    let source = format!("import \"{}\"\n", dir.join("my-lib.ne").display());
+    let mut ctx = Context::new();
+    let err = ctx.import(&dir.join("broken.ne"), "broken").unwrap_err();
+    assert_eq!(err.path, dir.join("broken.ne"));
+    assert_eq!(err.location.map(|(line, _)| line), Some(2));
+    let err = ctx.import(&dir.join("failing.ne"), "failing").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}:2:1: Undefined variable: 'missing'",
+            dir.join("failing.ne").display()
+        )
+    );
+    assert!(!ctx.vars.contains_key("failing.ok"));
+    let err = run(&mut ctx, &source).unwrap_err();
+    assert!(
+        err.to_string()
+            .ends_with("'my-lib' is not a valid namespace, name one with 'as'")
+    );
+}
+
+#[test]
+fn test_reimports_share_the_source() {
+    let dir = modules("reimport", &[("lib.ne", "f(x) = x + 1\n")]);
+    let source = |func: Option<&Func>| match func {
+        Some(Func::Imported { func, .. }) => match func.as_ref() {
+            Func::Custom(func) => func.pos.fragment().as_ptr(),
+            _ => panic!("Expected a user function"),
+        },
+        _ => panic!("Expected an imported function"),
+    };
+    let mut ctx = Context::new();
+    ctx.import(&dir.join("lib.ne"), "a").unwrap();
+    ctx.import(&dir.join("lib.ne"), "b").unwrap();
+    assert_eq!(source(ctx.funcs.get("a.f")), source(ctx.funcs.get("b.f")));
+    // An edited file is read again
+    fs::write(dir.join("lib.ne"), "f(x) = x + 2\n").unwrap();
+    ctx.import(&dir.join("lib.ne"), "c").unwrap();
+    assert_eq!(eval(&mut ctx, "a.f(1) + c.f(1)\n"), 5.0);
+}
@@ -84,6 +84,12 @@ fn test_program_execution_sequence() {
                     i
                 );
             }
+            Program::Comment(_) => unreachable!(),
+            Program::If(_)
+            | Program::While(_)
+            | Program::For(_)
+            | Program::Piecewise(_)
+            | Program::Import(_) => unreachable!(),
             Program::Var(token) => {
                 let eval_result = ctx.evaluate_expression(match &token.data.expr {
                     nelang::lang::VarAssignExpr::Expression(token) => token,
@@ -96,7 +102,7 @@ fn test_program_execution_sequence() {
                     input
                 );
                 let value = eval_result.unwrap();
-                ctx.vars.insert(token.data.ident.data.0.clone(), value);
+                ctx.vars.insert(token.data.ident.data.0.clone(), value.clone());
                 assert_eq!(
                     value.to_string(),
                     *expected,
@@ -0,0 +1,141 @@
+use nelang::lang::{Context, EvaluateExpressionError, Interval, Value};
+
+mod common;
+
+use common::{eval, evaluate};
+
+fn interval(lo: f64, hi: f64) -> Value {
+    Value::Interval(Interval::new(lo, hi).unwrap())
+}
+
+#[test]
+fn test_interval_arithmetic() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "[1 .. 2] + [3 .. 4]\n"), interval(4., 6.));
+    assert_eq!(eval(&mut ctx, "[1..2] - 1\n"), interval(0., 1.));
+    assert_eq!(eval(&mut ctx, "[1 .. 2] * [-3 .. 4]\n"), interval(-6., 8.));
+    assert_eq!(
+        eval(&mut ctx, "interval(1, 2) / [2 .. 4]\n"),
+        interval(0.25, 1.)
+    );
+    assert_eq!(eval(&mut ctx, "sqrt([4 .. 9])\n"), interval(2., 3.));
+    assert_eq!(eval(&mut ctx, "abs([-3 .. 1])\n"), interval(0., 3.));
+    assert_eq!(eval(&mut ctx, "width([1 .. 2.5])\n"), 1.5);
+    assert_eq!(eval(&mut ctx, "[1 .. 2] < [3 .. 4]\n"), 1.);
+    assert_eq!(eval(&mut ctx, "[1 .. 2]\n").to_string(), "[1 .. 2]");
+}
+
+#[test]
+fn test_interval_errors() {
+    let mut ctx = Context::new();
+    match evaluate(&mut ctx, "1 / [-1 .. 1]\n") {
+        Err(EvaluateExpressionError::DivisionByZero(expr)) => {
+            assert_eq!(expr.to_string(), "[-1 .. 1]")
+        }
+        other => panic!("Expected division by zero, got {other:?}"),
+    }
+    assert!(matches!(
+        evaluate(&mut ctx, "[1 .. 2] < [1.5 .. 4]\n"),
+        Err(EvaluateExpressionError::IndeterminateComparison(_))
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "[2 .. 1]\n"),
+        Err(EvaluateExpressionError::InvalidInterval(_))
+    ));
+}
+
+#[test]
+fn test_interval_equality() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "[1 .. 2] == [3 .. 4]\n"), 0.);
+    assert_eq!(eval(&mut ctx, "[1 .. 2] != 5\n"), 1.);
+    assert_eq!(eval(&mut ctx, "[2 .. 2] == 2\n"), 1.);
+    // Like '<', equality of overlapping intervals cannot be decided
+    for input in [
+        "[1 .. 2] == 1.5\n",
+        "[1..2] != [1..2]\n",
+        "[1 .. 2] == [2 .. 2]\n",
+    ] {
+        assert!(matches!(
+            evaluate(&mut ctx, input),
+            Err(EvaluateExpressionError::IndeterminateComparison(_))
+        ));
+    }
+
+    ctx.interval = true;
+    assert_eq!(eval(&mut ctx, "1 + 2 == 3\n"), 1.);
+    assert!(matches!(
+        evaluate(&mut ctx, "0.1 + 0.2 == 0.3\n"),
+        Err(EvaluateExpressionError::IndeterminateComparison(_))
+    ));
+}
+
+#[test]
+fn test_interval_mode_encloses_exact_result() {
+    let mut ctx = Context::new();
+    ctx.interval = true;
+
+    // 0.1 and 0.2 are not representable, so the result must enclose 0.3
+    let Value::Interval(sum) = eval(&mut ctx, "0.1 + 0.2\n") else {
+        panic!("Expected interval");
+    };
+    assert!(sum.lo < 0.3 && 0.3 < sum.hi);
+    assert!(sum.width() < 1e-15);
+
+    // Exactly representable inputs stay exact
+    assert_eq!(eval(&mut ctx, "0.5 * 3\n"), interval(1.5, 1.5));
+    assert_eq!(eval(&mut ctx, "100\n"), interval(100., 100.));
+    for input in ["1e3\n", "1_000\n", "1000.00\n", "0x3e8\n"] {
+        assert_eq!(evaluate(&mut ctx, input).unwrap(), interval(1000., 1000.));
+    }
+    // 2^53 + 1 is not a float
+    let Value::Interval(big) = eval(&mut ctx, "9007199254740993\n") else {
+        panic!("Expected interval");
+    };
+    assert!(!big.is_point());
+
+    let Value::Interval(third) = eval(&mut ctx, "1 / 3\n") else {
+        panic!("Expected interval");
+    };
+    assert!(third.lo < third.hi);
+    assert!(third.lo * 3. <= 1. && third.hi * 3. >= 1.);
+
+    let Value::Interval(root) = eval(&mut ctx, "sqrt(2)\n") else {
+        panic!("Expected interval");
+    };
+    assert!(root.lo * root.lo <= 2. && root.hi * root.hi >= 2.);
+}
+
+#[test]
+fn test_interval_mode_builtins() {
+    let mut ctx = Context::new();
+    ctx.interval = true;
+
+    // The peak of sine at π/2 and the trough of cosine at π are inside
+    let Value::Interval(sin) = eval(&mut ctx, "sin([0 .. 2])\n") else {
+        panic!("Expected interval");
+    };
+    assert!(sin.lo <= 0. && sin.hi == 1.);
+    let Value::Interval(cos) = eval(&mut ctx, "cos([1 .. 4])\n") else {
+        panic!("Expected interval");
+    };
+    assert!(cos.lo == -1. && cos.hi >= 1f64.cos() && cos.hi < 0.55);
+    let Value::Interval(zero) = eval(&mut ctx, "sin(0)\n") else {
+        panic!("Expected interval");
+    };
+    assert!(zero.contains_zero() && zero.width() < 1e-300);
+    assert_eq!(eval(&mut ctx, "cos([0 .. 7])\n"), interval(-1., 1.));
+
+    let Value::Interval(rounded) = eval(&mut ctx, "round([1.24 .. 1.26], 1)\n") else {
+        panic!("Expected interval");
+    };
+    assert!(rounded.lo <= 1.2 && 1.3 <= rounded.hi && rounded.width() < 0.11);
+    assert_eq!(eval(&mut ctx, "round(2.4)\n"), interval(2., 2.));
+
+    let Value::Interval(mean) = eval(&mut ctx, "mean(1, [2, [3 .. 4]])\n") else {
+        panic!("Expected interval");
+    };
+    assert!(mean.lo == 2. && 7. / 3. <= mean.hi && mean.width() < 0.34);
+    assert_eq!(eval(&mut ctx, "min([1 .. 3], 2)\n"), interval(1., 2.));
+    assert_eq!(eval(&mut ctx, "max([1 .. 3], 2)\n"), interval(2., 3.));
+}
@@ -0,0 +1,54 @@
+use nelang::lang::{Context, EvaluateExpressionError, Span, Value, program};
+
+mod common;
+
+use common::{define, eval, evaluate};
+
+#[test]
+fn test_let_bindings() {
+    let mut ctx = Context::new();
+    let value = eval(&mut ctx, "let a = 3 * 2, b = a + 1 in a * b\n");
+    assert_eq!(value, Value::Number(42.));
+    assert_eq!(eval(&mut ctx, "1 + let y = 2 in y * 3\n"), 7.);
+    define(
+        &mut ctx,
+        "f(x) = let a = x * 2,\n           b = a + 1\n       in a * b\n",
+    );
+    assert_eq!(eval(&mut ctx, "f(3)\n"), 42.);
+}
+
+#[test]
+fn test_let_shadowing() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "x = 10\n");
+    assert_eq!(eval(&mut ctx, "let x = x + 1 in x * 2\n"), 22.);
+    assert_eq!(eval(&mut ctx, "x\n"), 10.);
+    assert_eq!(eval(&mut ctx, "let a = 1, a = a + 1 in a\n"), 2.);
+    define(&mut ctx, "g(x) = let x = x * 10 in x + 1\n");
+    assert_eq!(eval(&mut ctx, "g(2)\n"), 21.);
+}
+
+#[test]
+fn test_undefined_locals_and_globals() {
+    let mut ctx = Context::new();
+    assert!(matches!(
+        evaluate(&mut ctx, "(let a = 1 in a) + a\n"),
+        Err(EvaluateExpressionError::UndefinedVar(_))
+    ));
+    define(&mut ctx, "rate = 2\nh(p) = p * rate\n");
+    let err = evaluate(&mut ctx, "h(1)\n").unwrap_err();
+    assert!(matches!(err, EvaluateExpressionError::UndefinedLocalVar(_)));
+    assert_eq!(
+        err.to_string(),
+        "Undefined variable: 'rate' (function bodies only see their arguments and 'let' bindings)"
+    );
+}
+
+#[test]
+fn test_let_syntax_errors() {
+    let Err(nom::Err::Failure(err)) = program(Span::new("let a = 1 a\n")) else {
+        panic!("expected a failure");
+    };
+    assert_eq!(err.message, "Expected 'in'");
+    assert!(program(Span::new("let in 1\n")).is_err());
+}
@@ -0,0 +1,71 @@
+use nelang::lang::{Context, Program, Span, program};
+
+fn evaluate(input: &str) -> Result<String, String> {
+    let ctx = Context::new();
+    match program(Span::new(input)) {
+        Ok((_, Program::Expression(token))) => ctx
+            .evaluate_expression(&token)
+            .map(|value| value.to_string())
+            .map_err(|err| err.to_string()),
+        Ok(_) => unreachable!(),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(format!(
+            "{} at column {}",
+            err.message,
+            err.input.get_column()
+        )),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[test]
+fn test_decimal_literals() {
+    assert_eq!(evaluate("1e-9\n"), Ok("0.000000001".to_string()));
+    assert_eq!(evaluate("1.5E3\n"), Ok("1500".to_string()));
+    assert_eq!(evaluate("2.5e+2\n"), Ok("250".to_string()));
+    assert_eq!(evaluate(".5 + .25\n"), Ok("0.75".to_string()));
+    assert_eq!(evaluate("0.001\n"), Ok("0.001".to_string()));
+    assert_eq!(
+        evaluate("1_000_000 + 0.000_5\n"),
+        Ok("1000000.0005".to_string())
+    );
+}
+
+#[test]
+fn test_radix_literals() {
+    assert_eq!(evaluate("0xFF\n"), Ok("255".to_string()));
+    assert_eq!(evaluate("0o17\n"), Ok("15".to_string()));
+    assert_eq!(evaluate("0b1010_1010\n"), Ok("170".to_string()));
+    assert_eq!(evaluate("0x1fi\n"), Ok("31i".to_string()));
+}
+
+#[test]
+fn test_literals_in_lists_and_intervals() {
+    assert_eq!(evaluate("[.5, 1e1]\n"), Ok("[0.5, 10]".to_string()));
+    assert_eq!(evaluate("[1 .. 2]\n"), Ok("[1 .. 2]".to_string()));
+}
+
+#[test]
+fn test_malformed_literals() {
+    let cases = [
+        ("0b102\n", "Invalid digit '2' in binary literal at column 5"),
+        ("0x\n", "Expected hexadecimal digits at column 3"),
+        (
+            "1__0\n",
+            "Digit separator should be followed by a digit at column 2",
+        ),
+        (
+            "10_\n",
+            "Digit separator should be followed by a digit at column 3",
+        ),
+        ("1.\n", "Expected digits after decimal point at column 3"),
+        ("2e-\n", "Expected exponent digits at column 4"),
+        ("1e400\n", "Float literal is too large at column 1"),
+        (
+            "99999999999999999999\n",
+            "Integer literal is too large at column 1",
+        ),
+    ];
+    for (input, message) in cases {
+        assert_eq!(evaluate(input), Err(message.to_string()), "{input}");
+    }
+}
@@ -0,0 +1,86 @@
+use nelang::lang::{Context, Decimal, DecimalMode, Func, Value};
+
+mod common;
+
+use common::{define, eval};
+
+#[test]
+fn test_memo_functions() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "memo fib(n) = (n < 2) ? n : fib(n - 1) + fib(n - 2)\n",
+    );
+    // Exponential without the cache
+    assert_eq!(eval(&mut ctx, "fib(70)\n"), 190392490709135.0);
+    {
+        let memo = ctx.memo.borrow();
+        assert_eq!(memo.len(), 71);
+        assert_eq!((memo.hits, memo.misses), (68, 71));
+    }
+    assert_eq!(eval(&mut ctx, "fib(70)\n"), 190392490709135.0);
+    assert_eq!(ctx.memo.borrow().hits, 69);
+    ctx.memo.get_mut().clear();
+    assert!(ctx.memo.borrow().is_empty());
+    assert_eq!(ctx.memo.borrow().hits, 0);
+}
+
+#[test]
+fn test_memo_piecewise_and_tail_calls() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "memo fact(0) = 1; fact(n) = n * fact(n - 1)
+memo count(n, acc) = (n == 0) ? acc : count(n - 1, acc + n)
+",
+    );
+    assert_eq!(eval(&mut ctx, "fact(10)\n"), 3628800.0);
+    assert_eq!(ctx.memo.borrow().len(), 11);
+    // Every call of the tail call chain shares its result
+    assert_eq!(eval(&mut ctx, "count(10, 0)\n"), 55.0);
+    assert_eq!(ctx.memo.borrow().len(), 22);
+    assert_eq!(eval(&mut ctx, "count(4, 45)\n"), 55.0);
+    assert_eq!(ctx.memo.borrow().len(), 22);
+}
+
+#[test]
+fn test_definitions_clear_results() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "scale(x) = x * 2\nmemo f(x) = scale(x) + 1\n");
+    assert_eq!(eval(&mut ctx, "f(3)\n"), 7.0);
+    define(&mut ctx, "scale(x) = x * 10\n");
+    assert!(ctx.memo.borrow().is_empty());
+    assert_eq!(eval(&mut ctx, "f(3)\n"), 31.0);
+    assert_eq!(eval(&mut ctx, "f(4)\n"), 41.0);
+    assert_eq!(ctx.memo.borrow().len(), 2);
+}
+
+#[test]
+fn test_memo_as_function_name() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "memo(x) = x + 1\ng(x) = x\n");
+    assert!(matches!(ctx.funcs.get("memo"), Some(Func::Custom(func)) if !func.data.memo));
+    assert_eq!(eval(&mut ctx, "memo(2) + g(1)\n"), 4.0);
+    assert!(ctx.memo.borrow().is_empty());
+    assert_eq!(ctx.memo.borrow().misses, 0);
+}
+
+#[test]
+fn test_results_follow_the_mode() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "memo third() = 1 / 3\nmemo small(x) = x < 1\n");
+    assert_eq!(eval(&mut ctx, "third()\n"), 1. / 3.);
+    assert_eq!(eval(&mut ctx, "small(0)\n"), 1.0);
+    ctx.decimal = Some(DecimalMode::default());
+    assert_eq!(
+        eval(&mut ctx, "third()\n"),
+        Value::Decimal(Decimal::new(33, 2))
+    );
+    ctx.decimal = None;
+    ctx.strict = true;
+    assert_eq!(eval(&mut ctx, "small(0)\n"), Value::Bool(true));
+    ctx.strict = false;
+    assert_eq!(eval(&mut ctx, "small(0)\n"), 1.0);
+    let memo = ctx.memo.borrow();
+    assert_eq!((memo.len(), memo.hits), (4, 1));
+}
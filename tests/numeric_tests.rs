@@ -0,0 +1,59 @@
+use nelang::lang::{Context, EvaluateExpressionError, Value};
+
+mod common;
+
+use common::{define, eval, evaluate};
+
+fn number(value: Value) -> f64 {
+    match value {
+        Value::Number(number) => number,
+        other => panic!("Expected number, got {other}"),
+    }
+}
+
+#[test]
+fn test_root_finding() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "f(x) = x * x - 2\n");
+    let root = number(eval(&mut ctx, "solve(f, 0, 2)\n"));
+    assert!((root - 2f64.sqrt()).abs() < 1e-9);
+    let root = number(eval(&mut ctx, "newton(f, 1)\n"));
+    assert!((root - 2f64.sqrt()).abs() < 1e-12);
+    let root = number(eval(&mut ctx, "solve(f, 0, 2, 0.5)\n"));
+    assert!((root - 2f64.sqrt()).abs() < 0.5);
+}
+
+#[test]
+fn test_integrate_and_minimize() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "g(x) = x * x\n");
+    let area = number(eval(&mut ctx, "integrate(g, 0, 3)\n"));
+    assert!((area - 9.).abs() < 1e-9);
+    let area = number(eval(&mut ctx, "integrate(sin, 0, 3.141592653589793)\n"));
+    assert!((area - 2.).abs() < 1e-9);
+    define(&mut ctx, "h(x) = (x - 1) * (x - 1) + 3\n");
+    let min = number(eval(&mut ctx, "minimize(h, -5, 5)\n"));
+    assert!((min - 1.).abs() < 1e-6);
+}
+
+#[test]
+fn test_numeric_errors() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "g(x) = x * x + 1\n");
+    assert!(matches!(
+        evaluate(&mut ctx, "solve(g, 0, 2)\n"),
+        Err(EvaluateExpressionError::NoBracket(_))
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "newton(g, 1)\n"),
+        Err(EvaluateExpressionError::NoConvergence(_, 100))
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "solve(g, 0, 2, 0)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(..))
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "integrate(ln, 0, 1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(..))
+    ));
+}
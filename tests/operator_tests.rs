@@ -0,0 +1,88 @@
+use nelang::lang::{Context, Program, Span, Value, program, script};
+
+mod common;
+
+use common::{define, eval};
+
+#[test]
+fn test_custom_operators() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "infixl 5 || (r1, r2) = r1 * r2 / (r1 + r2)
+infixl 6 <+> (a, b) = a + 2 * b
+",
+    );
+    assert_eq!(eval(&mut ctx, "10 || 10 || 5\n"), 2.5);
+    // Binds looser than '+' and the comparisons
+    assert_eq!(eval(&mut ctx, "6 || 3 + 3\n"), 3.0);
+    assert_eq!(eval(&mut ctx, "1 || 1 < 1\n"), 0.0);
+    assert_eq!(eval(&mut ctx, "1 <+> 2 * 3 <+> 1\n"), 15.0);
+    assert_eq!(eval(&mut ctx, "(1 <+> 2) || (3 <+> 1)\n"), 2.5);
+}
+
+#[test]
+fn test_right_associative_operators() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "infixr 8 ^ (a, b) = (b == 0) ? 1 : a * (a ^ (b - 1))
+infixr 5 ++ (a, b) = a + b
+",
+    );
+    assert_eq!(eval(&mut ctx, "2 ^ 3 ^ 2\n"), 512.0);
+    assert_eq!(eval(&mut ctx, "1 + 2 ^ 3 * 2\n"), 17.0);
+    assert_eq!(
+        eval(&mut ctx, "\"a\" ++ \"b\" ++ \"c\"\n").to_string(),
+        "abc"
+    );
+}
+
+#[test]
+fn test_builtin_precedence() {
+    let mut ctx = Context::new();
+    // Comparisons group with '+' and '-' from left to right
+    assert_eq!(eval(&mut ctx, "1 < 2 + 3\n"), 4.0);
+    assert_eq!(eval(&mut ctx, "2 + 3 == 5\n"), 1.0);
+    assert_eq!(eval(&mut ctx, "1 < (2 + 3)\n"), 1.0);
+    assert_eq!(eval(&mut ctx, "3 <= 3\n"), 1.0);
+    assert_eq!(eval(&mut ctx, "10 - 4 - 3\n"), 3.0);
+}
+
+#[test]
+fn test_errors() {
+    let err = script(Span::new("infixl 6 + (a, b) = a - b\n")).unwrap_err();
+    assert!(err.to_string().contains("Cannot redefine the operator '+'"));
+    let err = script(Span::new("infixl 6 <+> (a, b, c) = a\n")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Operator '<+>' must take 2 arguments")
+    );
+    let err = script(Span::new("infixl 10 <+> (a, b) = a\n")).unwrap_err();
+    assert!(err.to_string().contains("precedence must be 0-9"));
+    // Undeclared operators are not operators, and the words stay usable
+    assert!(script(Span::new("x = 1 <*> 2\n")).is_err());
+    let mut ctx = Context::new();
+    define(&mut ctx, "infixl = 2\ny = infixl * 3\n");
+    assert_eq!(ctx.vars.get("y"), Some(&Value::Number(6.)));
+}
+
+#[test]
+fn test_operators_belong_to_their_context() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "infixl 5 -- (a, b) = a * 100\n");
+    assert_eq!(eval(&mut ctx, "5 --2\n"), 500.0);
+    // Other contexts and plain parses still read '--' as two minuses
+    let mut other = Context::new();
+    assert_eq!(eval(&mut other, "5 --2\n"), 7.0);
+    let Program::Expression(expr) = program(Span::new("5 --2\n")).unwrap().1 else {
+        panic!("Expected expression");
+    };
+    assert_eq!(ctx.evaluate_expression(&expr).unwrap(), 7.0);
+    // A definition that fails to parse declares nothing
+    assert!(
+        ctx.script(Span::new("infixl 5 <+> (a, b) = a +\n"))
+            .is_err()
+    );
+    assert!(ctx.script(Span::new("x = 1 <+> 2\n")).is_err());
+}
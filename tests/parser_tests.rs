@@ -1,4 +1,4 @@
-use nelang::lang::{Context, Program, Span, program};
+use nelang::lang::{Context, Program, Span, Value, program};
 
 fn parse_and_evaluate(input: &str) -> Result<String, String> {
     let span = Span::new(input);
@@ -24,11 +24,17 @@ fn parse_and_evaluate(input: &str) -> Result<String, String> {
                     nelang::lang::VarAssignExpr::UserInput(_) => unreachable!(),
                 }) {
                     Ok(result) => {
-                        ctx.vars.insert(token.data.ident.data.0.clone(), result);
+                        ctx.vars.insert(token.data.ident.data.0.clone(), result.clone());
                         Ok(result.to_string())
                     }
                     Err(err) => Err(format!("{:?}", err)),
                 },
+                Program::Comment(_) => Ok(String::new()),
+                Program::If(_)
+                | Program::While(_)
+                | Program::For(_)
+                | Program::Piecewise(_)
+                | Program::Import(_) => unreachable!(),
             }
         }
         Err(err) => Err(format!("{:?}", err)),
@@ -73,9 +79,9 @@ mod tests {
                 nelang::lang::VarAssignExpr::Expression(token) => token,
                 nelang::lang::VarAssignExpr::UserInput(_) => unreachable!(),
             }).unwrap();
-            ctx.vars.insert(token.data.ident.data.0.clone(), result);
+            ctx.vars.insert(token.data.ident.data.0.clone(), result.clone());
             assert_eq!(result, 10.0);
-            assert_eq!(ctx.vars.get("x"), Some(&10.0));
+            assert_eq!(ctx.vars.get("x"), Some(&Value::Number(10.0)));
         } else {
             panic!("Expected variable assignment");
         }
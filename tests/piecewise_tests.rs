@@ -0,0 +1,87 @@
+use nelang::lang::{Context, EvaluateExpressionError, Func, Span, script};
+
+mod common;
+
+use common::{define, eval, evaluate};
+
+#[test]
+fn test_guards() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "abs2(x) | x < 0 = -x | otherwise = x
+sign(x)
+  | x < 0 = -1
+  | x > 0 = 1
+  | otherwise = 0
+",
+    );
+    assert_eq!(eval(&mut ctx, "abs2(-3)\n"), 3.0);
+    assert_eq!(eval(&mut ctx, "abs2(4)\n"), 4.0);
+    assert_eq!(
+        eval(&mut ctx, "sign(-7) + sign(0) * 10 + sign(2) * 100\n"),
+        99.0
+    );
+}
+
+#[test]
+fn test_literal_patterns() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "fact(0) = 1; fact(n) = n * fact(n - 1)
+fib(0) = 0
+fib(1) = 1
+fib(n) = fib(n - 1) + fib(n - 2)
+greet(\"en\") = \"hello\"; greet(\"fr\") = \"bonjour\"; greet(lang) = \"?\"
+",
+    );
+    assert_eq!(eval(&mut ctx, "fact(5)\n"), 120.0);
+    assert_eq!(eval(&mut ctx, "fib(10)\n"), 55.0);
+    assert_eq!(eval(&mut ctx, "greet(\"fr\")\n").to_string(), "bonjour");
+    assert_eq!(eval(&mut ctx, "greet(\"de\")\n").to_string(), "?");
+    let Some(Func::Piecewise(fib)) = ctx.funcs.get("fib") else {
+        panic!("Expected piecewise function");
+    };
+    assert_eq!(fib.data.clauses.len(), 3);
+    assert!(fib.data.is_exhaustive());
+}
+
+#[test]
+fn test_redefinition() {
+    let mut ctx = Context::new();
+    // Clauses are added until the function covers all arguments, after
+    // which a definition replaces it
+    define(&mut ctx, "f(0) = 1\nf(n) | n > 0 = 2\n");
+    let Some(Func::Piecewise(f)) = ctx.funcs.get("f") else {
+        panic!("Expected piecewise function");
+    };
+    assert!(!f.data.is_exhaustive());
+    define(&mut ctx, "f(n) = 3\n");
+    assert_eq!(eval(&mut ctx, "f(-1)\n"), 3.0);
+    define(&mut ctx, "f(n) = 4\n");
+    assert!(matches!(ctx.funcs.get("f"), Some(Func::Custom(_))));
+    assert_eq!(eval(&mut ctx, "f(0)\n"), 4.0);
+}
+
+#[test]
+fn test_errors() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "half(0) = 0\nhalf(n) | n > 0 = n / 2\n");
+    let err = evaluate(&mut ctx, "half(-4)\n").unwrap_err();
+    assert!(matches!(
+        err,
+        EvaluateExpressionError::NoMatchingClause(_, _)
+    ));
+    assert_eq!(
+        err.to_string(),
+        "No clause of 'half' matches the arguments (-4): 'half(-4)'"
+    );
+    let err = script(Span::new("g(0) = 1; h(n) = n\n")).unwrap_err();
+    assert!(err.to_string().contains("Expected another clause of 'g'"));
+    let err = script(Span::new("g(0) = 1; g(a, b) = a\n")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Clauses of 'g' must take 1 arguments")
+    );
+}
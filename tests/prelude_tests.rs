@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::Path;
+
+use nelang::lang::{Context, EvaluateExpressionError};
+
+mod common;
+
+use common::{TempDir, define, eval, evaluate};
+
+#[test]
+fn test_prelude_functions() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "clamp(5, 0, 3) + clamp(-1, 0, 3)\n"), 3.0);
+    assert_eq!(eval(&mut ctx, "lerp(10, 20, 0.25)\n"), 12.5);
+    assert_eq!(eval(&mut ctx, "rad2deg(deg2rad(90))\n"), 90.0);
+    assert_eq!(eval(&mut ctx, "sign(-2) + sign(0) + sign(7) * 10\n"), 9.0);
+    assert_eq!(eval(&mut ctx, "hypot(3, 4)\n"), 5.0);
+}
+
+#[test]
+fn test_bare_context() {
+    let mut ctx = Context::bare();
+    assert!(matches!(
+        evaluate(&mut ctx, "clamp(5, 0, 3)\n").unwrap_err(),
+        EvaluateExpressionError::UndefinedFunction(_)
+    ));
+    assert_eq!(eval(&mut ctx, "abs(-2)\n"), 2.0);
+    // Scripts may still replace prelude functions
+    let mut full = Context::new();
+    define(&mut full, "clamp(x, lo, hi) = 0\n");
+    assert_eq!(eval(&mut full, "clamp(5, 0, 3)\n"), 0.0);
+    define(&mut ctx, "clamp(x, lo, hi) = 1\n");
+    assert_eq!(eval(&mut ctx, "clamp(5, 0, 3)\n"), 1.0);
+}
+
+#[test]
+fn test_host_preludes() {
+    let temp = TempDir::new("prelude");
+    let dir = temp.path();
+    fs::write(
+        dir.join("circuit.ne"),
+        "total(r) = parallel(r, r) + lerp(0, 1, 0.5)\n",
+    )
+    .unwrap();
+    let source = format!("import \"{}\"\n", dir.join("circuit.ne").display());
+    let mut ctx = Context::new();
+    ctx.prelude(
+        Path::new("electric.ne"),
+        "const ohm = 1\nparallel(a, b) = a * b / (a + b)\n",
+    )
+    .unwrap();
+    assert_eq!(eval(&mut ctx, "parallel(4, 4) * ohm\n"), 2.0);
+    // Modules see the preludes, but do not export them again
+    define(&mut ctx, &source);
+    assert_eq!(eval(&mut ctx, "circuit.total(6)\n"), 3.5);
+    assert!(!ctx.funcs.contains_key("circuit.parallel"));
+    assert!(!ctx.funcs.contains_key("circuit.lerp"));
+    assert!(!ctx.vars.contains_key("circuit.ohm"));
+}
+
+#[test]
+fn test_prelude_errors() {
+    let mut ctx = Context::new();
+    let err = ctx
+        .prelude(Path::new("units.ne"), "ok(x) = x\nbad = missing\n")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "units.ne:2:1: Undefined variable: 'missing'"
+    );
+    let err = ctx
+        .prelude(Path::new("syntax.ne"), "f(x) = (x +\n")
+        .unwrap_err();
+    assert_eq!(err.location.map(|(line, _)| line), Some(1));
+}
@@ -0,0 +1,64 @@
+use nelang::lang::{Context, EvaluateExpressionError, Value};
+
+mod common;
+
+use common::{eval, evaluate};
+
+fn sequence(ctx: &mut Context) -> Vec<Value> {
+    [
+        "rand()\n",
+        "randint(1, 6)\n",
+        "normal(10, 2)\n",
+        "choice(\"a\", \"b\")\n",
+    ]
+    .into_iter()
+    .map(|input| evaluate(ctx, input).unwrap())
+    .collect()
+}
+
+#[test]
+fn test_seed_reproducibility() {
+    let mut first = Context::new();
+    first.seed(42);
+    let mut second = Context::new();
+    second.seed(42);
+    assert_eq!(sequence(&mut first), sequence(&mut second));
+    second.seed(7);
+    assert_ne!(sequence(&mut first), sequence(&mut second));
+}
+
+#[test]
+fn test_random_ranges() {
+    let mut ctx = Context::new();
+    ctx.seed(1);
+    for _ in 0..100 {
+        let Value::Number(number) = eval(&mut ctx, "rand()\n") else {
+            panic!("rand() should return a number");
+        };
+        assert!((0. ..1.).contains(&number));
+        let Value::Number(number) = eval(&mut ctx, "randint(-2, 2)\n") else {
+            panic!("randint() should return a number");
+        };
+        assert!((-2. ..=2.).contains(&number) && number.fract() == 0.);
+    }
+    assert_eq!(eval(&mut ctx, "randint(3, 3)\n"), 3.);
+    assert_eq!(eval(&mut ctx, "normal(5, 0)\n"), 5.);
+    assert_eq!(eval(&mut ctx, "choice([4])\n"), 4.);
+}
+
+#[test]
+fn test_random_errors() {
+    let mut ctx = Context::new();
+    for input in [
+        "randint(3, 1)\n",
+        "randint(1.5, 2)\n",
+        "normal(0, -1)\n",
+        "choice()\n",
+        "choice([])\n",
+    ] {
+        assert!(matches!(
+            evaluate(&mut ctx, input),
+            Err(EvaluateExpressionError::BuiltinFunctionError(..))
+        ));
+    }
+}
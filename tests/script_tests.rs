@@ -0,0 +1,61 @@
+use nelang::lang::{Context, Program, Span, Value, VarAssignExpr, script};
+
+fn run(source: &str) -> Value {
+    let mut ctx = Context::new();
+    let mut last = Value::Number(0.);
+    for program in script(Span::new(source)).unwrap().1 {
+        match program {
+            Program::Expression(token) => last = ctx.evaluate_expression(&token).unwrap(),
+            Program::Var(token) => {
+                let VarAssignExpr::Expression(expr) = &token.data.expr else {
+                    unreachable!()
+                };
+                last = ctx.evaluate_expression(expr).unwrap();
+                ctx.vars
+                    .insert(token.data.ident.data.0.clone(), last.clone());
+            }
+            Program::Func(_) | Program::Comment(_) => {}
+            Program::If(_)
+            | Program::While(_)
+            | Program::For(_)
+            | Program::Piecewise(_)
+            | Program::Import(_) => unreachable!(),
+        }
+    }
+    last
+}
+
+#[test]
+fn test_blank_lines_and_final_newline() {
+    assert_eq!(run("\n\nx = 2\n   \n\r\ny = x * 3\n\n"), Value::Number(6.));
+    assert_eq!(run("x = 2\nx + 1"), Value::Number(3.));
+    assert_eq!(run("x = 2 # no newline"), Value::Number(2.));
+    assert_eq!(run("x = 2\n  \t"), Value::Number(2.));
+    assert_eq!(
+        script(Span::new("x = 1\nprint(x)\n   ")).unwrap().1.len(),
+        2
+    );
+    assert!(script(Span::new("")).unwrap().1.is_empty());
+}
+
+#[test]
+fn test_continuation_lines() {
+    assert_eq!(run("total = (1 +\n  2) *\n  3\n"), Value::Number(9.));
+    assert_eq!(
+        run("y = (10\n     - 4 # six\n     / 2)\n"),
+        Value::Number(8.)
+    );
+    assert_eq!(run("max(1,\n  5,\n  3\n)\n"), Value::Number(5.));
+    assert_eq!(run("count([\n  1,\n\n  2\n])\n"), Value::Number(2.));
+    assert_eq!(run("((1 < 2)\n  ? 1\n  : 2)\n"), Value::Number(1.));
+}
+
+#[test]
+fn test_statements_stay_separate() {
+    // Outside brackets a line starting with an operator is a new statement
+    assert_eq!(run("x = 1\n-2\n"), Value::Number(-2.));
+    let programs = script(Span::new("x = 1\n-2\n")).unwrap().1;
+    assert_eq!(programs.len(), 2);
+    assert!(script(Span::new("x = (1 +\n")).is_err());
+    assert!(script(Span::new("x = 1 2\n")).is_err());
+}
@@ -0,0 +1,58 @@
+use nelang::lang::{Context, EvaluateExpressionError, Span, Value, program};
+
+mod common;
+
+use common::{eval, evaluate};
+
+fn list(values: &[f64]) -> Value {
+    Value::List(values.iter().map(|&value| Value::Number(value)).collect())
+}
+
+#[test]
+fn test_list_literals() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "[1, 2 + 3, 4]\n"), list(&[1., 5., 4.]));
+    assert_eq!(eval(&mut ctx, "[]\n"), list(&[]));
+    assert_eq!(eval(&mut ctx, "[1, 2]\n").to_string(), "[1, 2]");
+    assert!(program(Span::new("[1, 2\n")).is_err());
+}
+
+#[test]
+fn test_aggregates() {
+    let mut ctx = Context::new();
+    ctx.vars
+        .insert("data".to_string(), list(&[2., 4., 4., 4., 5., 5., 7., 9.]));
+    assert_eq!(eval(&mut ctx, "mean(data)\n"), 5.);
+    assert_eq!(eval(&mut ctx, "median(data)\n"), 4.5);
+    assert_eq!(eval(&mut ctx, "variance(data)\n"), 4.);
+    assert_eq!(eval(&mut ctx, "stddev(2, 4, 4, 4, 5, 5, 7, 9)\n"), 2.);
+    assert_eq!(eval(&mut ctx, "percentile(50, 1, 2, 3)\n"), 2.);
+    assert_eq!(eval(&mut ctx, "percentile(25, [1, 2, 3])\n"), 1.5);
+    assert_eq!(eval(&mut ctx, "min(data)\n"), 2.);
+    assert_eq!(eval(&mut ctx, "max(3, [1, 8])\n"), 8.);
+    assert_eq!(eval(&mut ctx, "count(data, 1)\n"), 9.);
+    assert_eq!(eval(&mut ctx, "count()\n"), 0.);
+    assert_eq!(eval(&mut ctx, "histogram(3, data)\n"), list(&[4., 2., 2.]));
+}
+
+#[test]
+fn test_empty_input() {
+    let mut ctx = Context::new();
+    for input in ["mean()\n", "median([])\n", "stddev([])\n", "max()\n"] {
+        assert!(matches!(
+            evaluate(&mut ctx, input),
+            Err(EvaluateExpressionError::BuiltinFunctionError(..))
+        ));
+    }
+    for input in [
+        "percentile(120, 1, 2)\n",
+        "histogram(0, 1)\n",
+        "histogram(2.5, 1)\n",
+        "histogram(1e20, 1)\n",
+    ] {
+        assert!(matches!(
+            evaluate(&mut ctx, input),
+            Err(EvaluateExpressionError::BuiltinFunctionError(..))
+        ));
+    }
+}
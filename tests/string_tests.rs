@@ -0,0 +1,85 @@
+use nelang::lang::{Context, EvaluateExpressionError, Span, Value, program};
+
+mod common;
+
+use common::{define, eval, evaluate};
+
+#[test]
+fn test_string_literals_and_escapes() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "\"hello\"\n"), Value::from("hello"));
+    assert_eq!(
+        eval(&mut ctx, "\"a\\tb\\n\\\"c\\\"\\\\\"\n"),
+        Value::from("a\tb\n\"c\"\\")
+    );
+    assert_eq!(eval(&mut ctx, "\"\"\n"), Value::from(""));
+}
+
+#[test]
+fn test_malformed_string_literals() {
+    let err = match program(Span::new("\"abc\\q\"\n")) {
+        Err(nom::Err::Failure(err)) => err,
+        other => panic!("Expected failure, got {other:?}"),
+    };
+    assert_eq!(err.message, "Unknown escape sequence");
+    assert_eq!(err.input.get_column(), 5);
+
+    let err = match program(Span::new("\"abc\n")) {
+        Err(nom::Err::Failure(err)) => err,
+        other => panic!("Expected failure, got {other:?}"),
+    };
+    assert_eq!(err.message, "Unterminated string literal");
+    assert_eq!(err.input.get_column(), 1);
+}
+
+#[test]
+fn test_string_operations() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "name = \"world\"\n");
+    assert_eq!(
+        eval(&mut ctx, "\"hello, \" + name\n"),
+        Value::from("hello, world")
+    );
+    assert_eq!(eval(&mut ctx, "len(name)\n"), 5.);
+    assert_eq!(eval(&mut ctx, "\"abc\" < \"abd\"\n"), 1.);
+    assert_eq!(eval(&mut ctx, "\"abc\" == \"abc\"\n"), 1.);
+    assert_eq!(eval(&mut ctx, "\"1\" == 1\n"), 0.);
+    assert_eq!(eval(&mut ctx, "str(1.5) + \"!\"\n"), Value::from("1.5!"));
+    assert_eq!(eval(&mut ctx, "name ? 1 : 2\n"), 1.);
+
+    assert!(matches!(
+        evaluate(&mut ctx, "\"a\" - 1\n"),
+        Err(EvaluateExpressionError::InvalidOperands(_))
+    ));
+}
+
+#[test]
+fn test_format_builtin() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "v = 12.5\n");
+    assert_eq!(
+        eval(&mut ctx, "format(\"{} m/s\", v)\n"),
+        Value::from("12.5 m/s")
+    );
+    assert_eq!(
+        eval(&mut ctx, "format(\"{{{}}} and {}\", \"x\", 2)\n"),
+        Value::from("{x} and 2")
+    );
+
+    assert!(matches!(
+        evaluate(&mut ctx, "format(\"{} {}\", 1)\n"),
+        Err(EvaluateExpressionError::BuiltinFunctionError(_, _))
+    ));
+}
+
+#[test]
+fn test_conversions_follow_the_number_format() {
+    let mut ctx = Context::new();
+    ctx.format.notation = "fixed 2".parse().unwrap();
+    ctx.format.separators = true;
+    assert_eq!(eval(&mut ctx, "str(1 / 3)\n"), Value::from("0.33"));
+    assert_eq!(
+        eval(&mut ctx, "format(\"{} and {}\", 12345, [1, \"a\"])\n"),
+        Value::from("12,345.00 and [1.00, a]")
+    );
+}
@@ -0,0 +1,66 @@
+use nelang::lang::{Context, DeriveError, Func};
+
+mod common;
+
+use common::{define, eval};
+
+fn derive(ctx: &mut Context, name: &str, var: &str) -> String {
+    let func = ctx.derive(name, var).unwrap();
+    let source = func.to_string();
+    ctx.funcs
+        .insert(func.data.ident.data.0.clone(), Func::Custom(func));
+    source
+}
+
+#[test]
+fn test_derive_polynomial() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "f(x) = x * x * x + 2 * x\n");
+    assert_eq!(derive(&mut ctx, "f", "x"), "f'(x) = 3 * x * x + 2");
+    assert_eq!(eval(&mut ctx, "f'(2)\n"), 14.);
+    assert_eq!(derive(&mut ctx, "f'", "x"), "f''(x) = 6 * x");
+}
+
+#[test]
+fn test_derive_builtins_and_branches() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "g(x) = (x > 0) ? x * x : -x\n");
+    assert_eq!(derive(&mut ctx, "g", "x"), "g'(x) = (x > 0) ? 2 * x : -1");
+    define(&mut ctx, "q(x) = 1 / (x * x + 1)\n");
+    assert_eq!(
+        derive(&mut ctx, "q", "x"),
+        "q'(x) = -(2 * x / ((x * x + 1) * (x * x + 1)))"
+    );
+    assert_eq!(eval(&mut ctx, "q'(1)\n"), -0.5);
+    define(&mut ctx, "h(x, y) = x * x * y + sin(y)\n");
+    assert_eq!(derive(&mut ctx, "h", "y"), "h'y(x, y) = x * x + cos(y)");
+    define(&mut ctx, "c(t) = h(t, 2 * t)\n");
+    assert_eq!(
+        derive(&mut ctx, "c", "t"),
+        "c'(t) = 4 * t * t + 2 * (t * t + cos(2 * t))"
+    );
+}
+
+#[test]
+fn test_derive_errors() {
+    let mut ctx = Context::new();
+    define(&mut ctx, "f(x) = x * x\n");
+    define(&mut ctx, "r(n) = (n < 1) ? 1 : n * r(n - 1)\n");
+    define(&mut ctx, "s(x) = len(x)\n");
+    assert!(matches!(
+        ctx.derive("f", "y"),
+        Err(DeriveError::UndefinedArgument(..))
+    ));
+    assert!(matches!(
+        ctx.derive("sin", "x"),
+        Err(DeriveError::NotCustomFunction(_))
+    ));
+    assert!(matches!(
+        ctx.derive("r", "n"),
+        Err(DeriveError::RecursiveFunction(_))
+    ));
+    assert!(matches!(
+        ctx.derive("s", "x"),
+        Err(DeriveError::Unsupported(_))
+    ));
+}
@@ -0,0 +1,78 @@
+use std::thread;
+
+use nelang::lang::{Context, EvaluateExpressionError};
+
+mod common;
+
+use common::{define, eval, evaluate};
+
+#[test]
+fn test_deep_tail_recursion() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "count(n, acc) = (n == 0) ? acc : count(n - 1, acc + n)\n",
+    );
+    assert_eq!(eval(&mut ctx, "count(1000000, 0)\n"), 500000500000.0);
+}
+
+#[test]
+fn test_tail_position_through_let_and_guards() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "down(n) | n < 1 = 0 | otherwise = let m = n - 1 in down(m)
+even(n) = (n == 0) ? 1 : odd(n - 1)
+odd(n) = (n == 0) ? 0 : even(n - 1)
+",
+    );
+    assert_eq!(eval(&mut ctx, "down(100000)\n"), 0.0);
+    assert_eq!(eval(&mut ctx, "even(100001)\n"), 0.0);
+    assert_eq!(eval(&mut ctx, "odd(100001)\n"), 1.0);
+}
+
+#[test]
+fn test_calls_outside_tail_position() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "fact(n) = (n < 2) ? 1 : n * fact(n - 1)
+twice(n) = 2 * count(n)
+count(n) = (n == 0) ? 0 : 1 + count(n - 1)
+",
+    );
+    assert_eq!(eval(&mut ctx, "fact(10)\n"), 3628800.0);
+    assert_eq!(eval(&mut ctx, "twice(30)\n"), 60.0);
+}
+
+#[test]
+fn test_deep_recursion_outside_tail_position() {
+    // As deep as before tail calls, on a stack the size of the main thread's
+    thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(|| {
+            let mut ctx = Context::new();
+            define(&mut ctx, "depth(n) = (n == 0) ? 0 : 1 + depth(n - 1)\n");
+            assert_eq!(eval(&mut ctx, "depth(700)\n"), 700.0);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_errors_in_tail_calls() {
+    let mut ctx = Context::new();
+    define(
+        &mut ctx,
+        "f(n) = (n == 0) ? missing(n) : f(n - 1)\ng(n) = (n == 0) ? sqrt(1, 2) : g(n - 1)\n",
+    );
+    assert!(matches!(
+        evaluate(&mut ctx, "f(1000)\n").unwrap_err(),
+        EvaluateExpressionError::UndefinedFunction(_)
+    ));
+    assert!(matches!(
+        evaluate(&mut ctx, "g(1000)\n").unwrap_err(),
+        EvaluateExpressionError::InvalidFunctionArgc(_, 1)
+    ));
+}
@@ -0,0 +1,75 @@
+use nelang::lang::{Context, Dimension, EvaluateExpressionError, Unit};
+
+mod common;
+
+use common::{eval, evaluate};
+
+#[test]
+fn test_unit_literals_and_arithmetic() {
+    let mut ctx = Context::new();
+    assert_eq!(eval(&mut ctx, "5 km\n").to_string(), "5 km");
+    assert_eq!(eval(&mut ctx, "5 km + 300 m\n").to_string(), "5.3 km");
+    assert_eq!(eval(&mut ctx, "2 * 3 m\n").to_string(), "6 m");
+    assert_eq!(
+        eval(&mut ctx, "9.81 m/s^2 * 2 kg\n").to_string(),
+        "19.62 m*kg/s^2"
+    );
+    // Quantities without a dimension are plain numbers
+    assert_eq!(eval(&mut ctx, "1 m / 2 m\n"), 0.5);
+    assert_eq!(eval(&mut ctx, "5 km > 300 m\n"), 1.);
+    assert_eq!(eval(&mut ctx, "1 km == 1000 m\n"), 1.);
+    assert_eq!(eval(&mut ctx, "sqrt(16 m^2)\n").to_string(), "4 m");
+}
+
+#[test]
+fn test_unit_conversion() {
+    let mut ctx = Context::new();
+    assert_eq!(
+        evaluate(&mut ctx, "to(100 km / 2 h, km/h)\n")
+            .unwrap()
+            .to_string(),
+        "50 km/h"
+    );
+    assert_eq!(eval(&mut ctx, "to(1500 m, km)\n").to_string(), "1.5 km");
+    assert!(matches!(
+        evaluate(&mut ctx, "to(1500 m, s)\n"),
+        Err(EvaluateExpressionError::DimensionMismatch(..))
+    ));
+}
+
+#[test]
+fn test_dimension_mismatch() {
+    let mut ctx = Context::new();
+    match evaluate(&mut ctx, "5 m + 3 s\n") {
+        Err(EvaluateExpressionError::DimensionMismatch(expr, lhs, rhs)) => {
+            assert_eq!(expr.to_string(), "5 m + 3 s");
+            assert_eq!((lhs, rhs), (Dimension::LENGTH, Dimension::TIME));
+        }
+        other => panic!("Expected dimension mismatch, got {other:?}"),
+    }
+    assert!(matches!(
+        evaluate(&mut ctx, "1 m < 1\n"),
+        Err(EvaluateExpressionError::DimensionMismatch(..))
+    ));
+    match evaluate(&mut ctx, "3 furlong\n") {
+        Err(EvaluateExpressionError::UndefinedUnit(ident)) => {
+            assert_eq!(ident.to_string(), "furlong")
+        }
+        other => panic!("Expected undefined unit, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_custom_units() {
+    let mut ctx = Context::new();
+    ctx.define_unit("ft", Unit::new(0.3048, Dimension::LENGTH));
+    ctx.define_unit(
+        "mph",
+        Unit::new(1609.344, Dimension::LENGTH) / Unit::new(3600., Dimension::TIME),
+    );
+    assert_eq!(
+        eval(&mut ctx, "to(3 ft, m)\n").to_string(),
+        "0.9144000000000001 m"
+    );
+    assert_eq!(eval(&mut ctx, "60 mph * 1 h > 96 km\n"), 1.);
+}